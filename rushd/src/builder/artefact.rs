@@ -35,6 +35,9 @@ impl Artefact {
 
     pub fn render_to_file(&self, context: &BuildContext) {
         let rendered = self.render(context);
+        if let Some(parent) = std::path::Path::new(&self.output_path).parent() {
+            std::fs::create_dir_all(parent).expect("Failed to create output directory");
+        }
         std::fs::write(&self.output_path, rendered).expect("Failed to write to output file");
     }
 }
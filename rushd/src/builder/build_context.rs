@@ -1,9 +1,10 @@
-use crate::builder::BuildType;
+use crate::builder::{BuildType, Resources};
 use serde::{Deserialize, Serialize};
 
 use crate::container::ServicesSpec;
 use crate::toolchain::Platform;
 use crate::ToolchainContext;
+use std::collections::BTreeMap;
 use std::str;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -15,6 +16,24 @@ pub struct BuildContext {
     pub rust_target: String,
     pub toolchain: ToolchainContext,
     pub services: ServicesSpec,
+    /// `<COMPONENT>_HOST`, `_PORT` and `_URL` of every component with
+    /// manifests, addressed by its in-cluster DNS name, except those the
+    /// component sets itself
+    pub discovery: BTreeMap<String, String>,
+    /// Port of the component's own Service in Kubernetes
+    pub cluster_port: Option<u16>,
+    /// The component's own environment, from its `env_file` and `env`,
+    /// without the variables listed in `secret_env`
+    pub env: BTreeMap<String, String>,
+    /// Names of the secret variables, whose values are only in the
+    /// component's Secret
+    pub secret_env: Vec<String>,
+    /// CPU and memory of the component, as Kubernetes quantities
+    pub resources: Resources,
+    /// Distinct subdomains of the services, for host based routing
+    pub subdomains: Vec<String>,
+    /// Whether the ingress serves HTTPS with the development certificates
+    pub tls: bool,
 
     pub environment: String,
     pub domain: String,
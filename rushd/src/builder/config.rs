@@ -52,6 +52,13 @@ impl Config {
     pub fn root_path(&self) -> &str {
         &self.root_path
     }
+
+    /// The same config with the product served under another domain
+    pub fn with_domain(&self, domain: &str) -> Arc<Self> {
+        let mut config = self.clone();
+        config.domain = domain.to_string();
+        Arc::new(config)
+    }
     
     pub fn new(
         root_path: &str,
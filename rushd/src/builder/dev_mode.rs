@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// How a component is run by `rushd dev`. `Native` runs the component as a
+/// process on the host instead of building and starting a container.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DevMode {
+    #[default]
+    Docker,
+    Native,
+}
+
+impl DevMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim().to_lowercase().as_str() {
+            "docker" => Some(Self::Docker),
+            "native" => Some(Self::Native),
+            _ => None,
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        *self == DevMode::Native
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// How an Ingress component is served by `rushd dev`. `Builtin` routes
+/// requests with rushd's own reverse proxy instead of building and running
+/// the component's Dockerfile.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IngressMode {
+    #[default]
+    Container,
+    Builtin,
+}
+
+impl IngressMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim().to_lowercase().as_str() {
+            "container" => Some(Self::Container),
+            "builtin" => Some(Self::Builtin),
+            _ => None,
+        }
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        *self == IngressMode::Builtin
+    }
+}
@@ -3,6 +3,10 @@ mod build_context;
 mod build_script;
 mod build_type;
 mod config;
+mod dev_mode;
+mod ingress_mode;
+mod references;
+mod resources;
 mod spec;
 mod templates;
 mod variables;
@@ -14,5 +18,9 @@ pub use build_context::BuildContext;
 pub use build_script::BuildScript;
 pub use build_type::BuildType;
 pub use config::Config;
-pub use spec::ComponentBuildSpec;
+pub use dev_mode::DevMode;
+pub use ingress_mode::IngressMode;
+pub use references::References;
+pub use resources::Resources;
+pub use spec::{ComponentBuildSpec, VOLUME_PREFIX};
 pub use variables::Variables;
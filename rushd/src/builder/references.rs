@@ -0,0 +1,228 @@
+use crate::builder::ComponentBuildSpec;
+use crate::container::ServicesSpec;
+use std::collections::HashMap;
+
+/// Resolves references to other components in stack values, such as
+/// `{{ components.database.env.POSTGRES_USER }}` or
+/// `{{ services.database.target_port }}`. References are resolved once all
+/// components are loaded and their services are known. Env values may
+/// themselves hold references, which are followed.
+pub struct References<'a> {
+    env: HashMap<String, HashMap<String, String>>,
+    services: &'a ServicesSpec,
+    resolved: HashMap<(String, String), String>,
+}
+
+impl<'a> References<'a> {
+    pub fn new(specs: &[ComponentBuildSpec], services: &'a ServicesSpec) -> Self {
+        let env = specs
+            .iter()
+            .map(|spec| (spec.component_name.clone(), spec.env.clone().unwrap_or_default()))
+            .collect();
+        Self::from_env(env, services)
+    }
+
+    fn from_env(env: HashMap<String, HashMap<String, String>>, services: &'a ServicesSpec) -> Self {
+        References {
+            env,
+            services,
+            resolved: HashMap::new(),
+        }
+    }
+
+    /// Whether a template expression, without braces, is a reference.
+    pub fn is_reference(expression: &str) -> bool {
+        expression.starts_with("components.") || expression.starts_with("services.")
+    }
+
+    /// Replaces the references in `input`. Other `{{ }}` expressions are
+    /// left as they are.
+    pub fn resolve(&mut self, input: &str) -> Result<String, String> {
+        self.resolve_with(input, &mut Vec::new())
+    }
+
+    fn resolve_with(&mut self, input: &str, stack: &mut Vec<String>) -> Result<String, String> {
+        let mut output = String::new();
+        let mut rest = input;
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+            let expression = rest[start + 2..end].trim();
+            output.push_str(&rest[..start]);
+            if Self::is_reference(expression) {
+                output.push_str(&self.lookup(expression, stack)?);
+            } else {
+                output.push_str(&rest[start..end + 2]);
+            }
+            rest = &rest[end + 2..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn lookup(&mut self, expression: &str, stack: &mut Vec<String>) -> Result<String, String> {
+        let parts = expression.splitn(4, '.').collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["components", component, "env", key] => self.resolve_env(component, key, stack),
+            ["services", component, field] => {
+                let service = self.services.get(*component).ok_or(format!(
+                    "`{}` refers to `{}`, which is not a component with a port",
+                    expression, component
+                ))?;
+                let value = serde_json::to_value(service).map_err(|e| e.to_string())?;
+                match value.get(*field) {
+                    Some(serde_json::Value::String(value)) => Ok(value.clone()),
+                    Some(serde_json::Value::Number(value)) => Ok(value.to_string()),
+                    Some(serde_json::Value::Null) => Err(format!("`{}` is not set", expression)),
+                    _ => Err(format!("`{}` refers to unknown service field `{}`", expression, field)),
+                }
+            }
+            _ => Err(format!(
+                "Invalid reference `{}`, expected components.<name>.env.<KEY> or services.<name>.<field>",
+                expression
+            )),
+        }
+    }
+
+    fn resolve_env(&mut self, component: &str, key: &str, stack: &mut Vec<String>) -> Result<String, String> {
+        let id = (component.to_string(), key.to_string());
+        if let Some(value) = self.resolved.get(&id) {
+            return Ok(value.clone());
+        }
+
+        let name = format!("components.{}.env.{}", component, key);
+        if stack.contains(&name) {
+            stack.push(name);
+            return Err(format!("Reference cycle: {}", stack.join(" -> ")));
+        }
+
+        let value = self
+            .env
+            .get(component)
+            .ok_or(format!("`{}` refers to unknown component `{}`", name, component))?
+            .get(key)
+            .ok_or(format!("`{}` is not set, `{}` has no env variable {}", name, component, key))?
+            .clone();
+
+        stack.push(name);
+        let value = self.resolve_with(&value, stack)?;
+        stack.pop();
+
+        self.resolved.insert(id, value.clone());
+        Ok(value)
+    }
+
+    /// Resolves the env values of a component.
+    pub fn resolve_component_env(&mut self, component: &str) -> Result<HashMap<String, String>, String> {
+        let keys = self.env.get(component).map(|env| env.keys().cloned().collect::<Vec<_>>()).unwrap_or_default();
+        let mut env = HashMap::new();
+        for key in keys {
+            let value = self.resolve_env(component, &key, &mut Vec::new())?;
+            env.insert(key, value);
+        }
+        Ok(env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::ServiceSpec;
+
+    fn env(components: &[(&str, &[(&str, &str)])]) -> HashMap<String, HashMap<String, String>> {
+        components
+            .iter()
+            .map(|(component, vars)| {
+                let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+                (component.to_string(), vars)
+            })
+            .collect()
+    }
+
+    fn services() -> ServicesSpec {
+        let database = ServiceSpec {
+            name: "database".to_string(),
+            host: "database".to_string(),
+            port: 15432,
+            target_port: 5432,
+            mount_point: None,
+            subdomain: None,
+            cluster_port: None,
+        };
+        HashMap::from([("database".to_string(), database)])
+    }
+
+    #[test]
+    fn resolves_env_and_service_references() {
+        let services = services();
+        let env = env(&[
+            ("database", &[("POSTGRES_USER", "admin")]),
+            ("backend", &[("DATABASE_URL", "postgres://{{ components.database.env.POSTGRES_USER }}@database:{{ services.database.target_port }}")]),
+        ]);
+        let mut references = References::from_env(env, &services);
+
+        let resolved = references.resolve_component_env("backend").unwrap();
+        assert_eq!(resolved["DATABASE_URL"], "postgres://admin@database:5432");
+    }
+
+    #[test]
+    fn leaves_other_expressions_untouched() {
+        let services = services();
+        let mut references = References::from_env(env(&[]), &services);
+
+        assert_eq!(references.resolve("{{ environment }}-{{ services.database.port }}").unwrap(), "{{ environment }}-15432");
+        assert_eq!(references.resolve("unclosed {{ services.database.port").unwrap(), "unclosed {{ services.database.port");
+    }
+
+    #[test]
+    fn reports_a_cycle() {
+        let services = services();
+        let env = env(&[
+            ("a", &[("X", "{{ components.b.env.Y }}")]),
+            ("b", &[("Y", "{{ components.a.env.X }}")]),
+        ]);
+        let mut references = References::from_env(env, &services);
+
+        assert_eq!(
+            references.resolve_component_env("a").unwrap_err(),
+            "Reference cycle: components.a.env.X -> components.b.env.Y -> components.a.env.X"
+        );
+    }
+
+    #[test]
+    fn reports_a_self_reference() {
+        let services = services();
+        let env = env(&[("a", &[("X", "x{{ components.a.env.X }}")])]);
+        let mut references = References::from_env(env, &services);
+
+        assert_eq!(
+            references.resolve_component_env("a").unwrap_err(),
+            "Reference cycle: components.a.env.X -> components.a.env.X"
+        );
+    }
+
+    #[test]
+    fn reports_missing_components_and_keys() {
+        let services = services();
+        let mut references = References::from_env(env(&[("a", &[])]), &services);
+
+        assert_eq!(
+            references.resolve("{{ components.b.env.X }}").unwrap_err(),
+            "`components.b.env.X` refers to unknown component `b`"
+        );
+        assert_eq!(
+            references.resolve("{{ components.a.env.X }}").unwrap_err(),
+            "`components.a.env.X` is not set, `a` has no env variable X"
+        );
+        assert_eq!(
+            references.resolve("{{ services.a.port }}").unwrap_err(),
+            "`services.a.port` refers to `a`, which is not a component with a port"
+        );
+        assert_eq!(
+            references.resolve("{{ services.database.mount_point }}").unwrap_err(),
+            "`services.database.mount_point` is not set"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+/// CPU and memory budget of a component, written as Kubernetes quantities
+/// such as `500m` and `512Mi`. Manifests get them as they are, dev
+/// containers as `--cpus` and `--memory`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Resources {
+    pub cpu: Option<String>,
+    pub memory: Option<String>,
+}
+
+impl Resources {
+    /// Reads the `resources` of a component. `cpu` and `memory` at the top
+    /// apply to every environment, and a section named after an environment
+    /// overrides them:
+    ///
+    /// ```yaml
+    /// resources:
+    ///   cpu: "500m"
+    ///   memory: "256Mi"
+    ///   prod:
+    ///     memory: "1Gi"
+    /// ```
+    pub fn from_yaml(value: &serde_yaml::Value, environment: &str) -> Result<Self, String> {
+        let mapping = value.as_mapping().ok_or("resources must be a mapping")?;
+        let mut resources = Resources::default();
+        resources.apply(mapping)?;
+        for (key, section) in mapping {
+            let key = key.as_str().ok_or("resources keys must be strings")?;
+            if key == "cpu" || key == "memory" {
+                continue;
+            }
+            let section = section
+                .as_mapping()
+                .ok_or(format!("resources.{} must hold cpu and memory", key))?;
+            if key == environment {
+                resources.apply(section)?;
+            }
+        }
+        Ok(resources)
+    }
+
+    fn apply(&mut self, mapping: &serde_yaml::Mapping) -> Result<(), String> {
+        if let Some(cpu) = mapping.get("cpu") {
+            let cpu = quantity(cpu).ok_or("cpu must be a number or a quantity")?;
+            cpus(&cpu).ok_or(format!("Invalid cpu: {}", cpu))?;
+            self.cpu = Some(cpu);
+        }
+        if let Some(memory) = mapping.get("memory") {
+            let memory = quantity(memory).ok_or("memory must be a number or a quantity")?;
+            bytes(&memory).ok_or(format!("Invalid memory: {}", memory))?;
+            self.memory = Some(memory);
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cpu.is_none() && self.memory.is_none()
+    }
+
+    /// Number of CPUs, as `docker run --cpus` and Compose take it
+    pub fn cpus(&self) -> Option<f64> {
+        self.cpu.as_deref().and_then(cpus)
+    }
+
+    /// Memory in bytes, as `docker run --memory` and Compose take it
+    pub fn memory_bytes(&self) -> Option<u64> {
+        self.memory.as_deref().and_then(bytes)
+    }
+
+    pub fn docker_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(cpus) = self.cpus() {
+            args.push("--cpus".to_string());
+            args.push(cpus.to_string());
+        }
+        if let Some(memory) = self.memory_bytes() {
+            args.push("--memory".to_string());
+            args.push(memory.to_string());
+        }
+        args
+    }
+}
+
+fn quantity(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(value) => Some(value.trim().to_string()),
+        serde_yaml::Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// `500m` is half a CPU, `2` or `0.5` a number of CPUs.
+fn cpus(quantity: &str) -> Option<f64> {
+    let cpus = match quantity.strip_suffix('m') {
+        Some(millis) => millis.parse::<f64>().ok()? / 1000.0,
+        None => quantity.parse::<f64>().ok()?,
+    };
+    (cpus > 0.0).then_some(cpus)
+}
+
+/// Memory with a binary (`Ki`, `Mi`, `Gi`, `Ti`) or decimal (`k`, `M`, `G`,
+/// `T`) suffix, or plain bytes.
+fn bytes(quantity: &str) -> Option<u64> {
+    const SUFFIXES: [(&str, u64); 8] = [
+        ("Ki", 1 << 10),
+        ("Mi", 1 << 20),
+        ("Gi", 1 << 30),
+        ("Ti", 1 << 40),
+        ("k", 1_000),
+        ("M", 1_000_000),
+        ("G", 1_000_000_000),
+        ("T", 1_000_000_000_000),
+    ];
+    let (number, multiplier) = SUFFIXES
+        .iter()
+        .find_map(|(suffix, multiplier)| quantity.strip_suffix(suffix).map(|number| (number, *multiplier)))
+        .unwrap_or((quantity, 1));
+    let bytes = number.parse::<f64>().ok()? * multiplier as f64;
+    (bytes >= 1.0).then_some(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpus() {
+        assert_eq!(cpus("500m"), Some(0.5));
+        assert_eq!(cpus("2"), Some(2.0));
+        assert_eq!(cpus("0.25"), Some(0.25));
+        assert_eq!(cpus("0"), None);
+        assert_eq!(cpus("-1"), None);
+        assert_eq!(cpus("half"), None);
+    }
+
+    #[test]
+    fn parses_bytes() {
+        assert_eq!(bytes("512Mi"), Some(512 * 1024 * 1024));
+        assert_eq!(bytes("1.5Gi"), Some(3 * 512 * 1024 * 1024));
+        assert_eq!(bytes("1k"), Some(1_000));
+        assert_eq!(bytes("2G"), Some(2_000_000_000));
+        assert_eq!(bytes("1024"), Some(1024));
+        assert_eq!(bytes("0"), None);
+        assert_eq!(bytes("12Mb"), None);
+    }
+
+    #[test]
+    fn environment_section_overrides_defaults() {
+        let yaml = serde_yaml::from_str("cpu: \"500m\"\nmemory: 256Mi\nprod:\n  memory: 1Gi\n").unwrap();
+
+        let dev = Resources::from_yaml(&yaml, "dev").unwrap();
+        assert_eq!(dev.docker_args(), ["--cpus", "0.5", "--memory", "268435456"]);
+
+        let prod = Resources::from_yaml(&yaml, "prod").unwrap();
+        assert_eq!(prod.cpu.as_deref(), Some("500m"));
+        assert_eq!(prod.memory.as_deref(), Some("1Gi"));
+    }
+}
@@ -1,14 +1,68 @@
 use crate::builder::Artefact;
 use crate::builder::BuildContext;
 use crate::builder::Config;
-use crate::builder::{BuildScript, BuildType};
-use crate::container::{ServiceSpec, ServicesSpec};
+use crate::builder::{BuildScript, BuildType, DevMode, IngressMode, References, Resources};
+use crate::container::{discovery_env, LogFormat, ServiceSpec, ServicesSpec};
 use crate::ToolchainContext;
 use std::collections::HashMap;
 use std::sync::Arc;
+use tera::{Context, Tera};
 
 use super::Variables;
 
+/// Stop signal and timeout used when the stack does not set them, matching
+/// the defaults of `docker stop`.
+const DEFAULT_STOP_SIGNAL: &str = "TERM";
+const DEFAULT_STOP_TIMEOUT: u64 = 10;
+
+/// Prefix of a volume source that names a Docker volume. Every other
+/// source is a host path relative to the product directory.
+pub const VOLUME_PREFIX: &str = "volume:";
+
+/// Reads the `env_file` of a component, a path or a list of paths relative
+/// to the product directory. Paths are templates over the config, such as
+/// `backend.{{ environment }}.env`, and later files override earlier ones.
+fn load_env_files(config: &Config, value: &serde_yaml::Value) -> HashMap<String, String> {
+    let templates = match value {
+        serde_yaml::Value::String(path) => vec![path.clone()],
+        serde_yaml::Value::Sequence(paths) => paths
+            .iter()
+            .map(|path| path.as_str().expect("env_file entries must be paths").to_string())
+            .collect(),
+        _ => panic!("env_file must be a path or a list of paths"),
+    };
+
+    let context = Context::from_serialize(config).expect("Could not create config context");
+    let mut env = HashMap::new();
+    for template in templates {
+        let path = Tera::one_off(&template, &context, false)
+            .unwrap_or_else(|e| panic!("Could not render env_file {}: {}", template, e));
+        let contents = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Could not read env_file {}: {}", path, e));
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line
+                .split_once('=')
+                .unwrap_or_else(|| panic!("Could not parse env_file {}, line {}: expected KEY=VALUE", path, number + 1));
+            env.insert(key.trim().to_string(), unquote(value.trim()).to_string());
+        }
+    }
+    env
+}
+
+/// Values in env files may be wrapped in single or double quotes.
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
 #[derive(Debug, Clone)]
 pub struct ComponentBuildSpec {
     pub build_type: BuildType,
@@ -25,11 +79,24 @@ pub struct ComponentBuildSpec {
     pub artefact_output_dir: String,
     pub docker_extra_run_args: Vec<String>,
     pub env: Option<HashMap<String, String>>,
+    /// Names of the `env` variables whose values are secret. They are kept
+    /// out of the rendered manifests.
+    pub secret_env: Vec<String>,
     pub volumes: Option<HashMap<String, String>>,
     pub port: Option<u16>,
     pub target_port: Option<u16>,
+    pub https_port: Option<u16>,
     pub k8s: Option<String>, // TODO: Refactor to k8s_dir
     pub priority: u64,
+    pub log_format: LogFormat,
+    pub dev_mode: DevMode,
+    pub ingress_mode: IngressMode,
+    /// Signal sent to stop the component, without the `SIG` prefix
+    pub stop_signal: String,
+    /// Seconds to wait for the component to exit before it is killed
+    pub stop_timeout: u64,
+    /// CPU and memory for the current environment
+    pub resources: Resources,
 
 
     // Set after loading
@@ -54,6 +121,12 @@ impl ComponentBuildSpec {
 
     pub fn from_yaml(config: Arc<Config>, variables: Arc<Variables>, yaml_section: &serde_yaml::Value) -> Self {
         let product_name = config.product_name();
+        let ingress_mode = yaml_section
+            .get("ingress_mode")
+            .map_or(IngressMode::default(), |v| {
+                let mode = Self::process_template_string(v.as_str().unwrap(), &variables);
+                IngressMode::parse(&mode).unwrap_or_else(|| panic!("Invalid ingress_mode: {}", mode))
+            });
         let build_type = match yaml_section
             .get("build_type")
             .expect("build_type is required")
@@ -121,12 +194,12 @@ impl ComponentBuildSpec {
                     .iter()
                     .map(|v| v.as_str().unwrap().to_string())
                     .collect(),
-                dockerfile_path: yaml_section
-                    .get("dockerfile")
-                    .expect("dockerfile_path is required")
-                    .as_str()
-                    .unwrap()
-                    .to_string(),
+                // The built-in proxy does not need an image
+                dockerfile_path: match yaml_section.get("dockerfile") {
+                    Some(v) => v.as_str().unwrap().to_string(),
+                    None if ingress_mode.is_builtin() => String::new(),
+                    None => panic!("dockerfile_path is required"),
+                },
             },
             "Image" => BuildType::PureDockerImage {
                 image_name_with_tag: yaml_section
@@ -164,6 +237,31 @@ impl ComponentBuildSpec {
             _ => panic!("Invalid build_type"),
         };
 
+        // Frontends are served by `trunk serve` in dev unless asked otherwise
+        let default_dev_mode = match build_type {
+            BuildType::TrunkWasm { .. } => DevMode::Native,
+            _ => DevMode::Docker,
+        };
+        let dev_mode = yaml_section
+            .get("dev_mode")
+            .map_or(default_dev_mode, |v| {
+                let mode = Self::process_template_string(v.as_str().unwrap(), &variables);
+                DevMode::parse(&mode).unwrap_or_else(|| panic!("Invalid dev_mode: {}", mode))
+            });
+        if dev_mode.is_native() && !matches!(build_type, BuildType::RustBinary { .. } | BuildType::TrunkWasm { .. }) {
+            panic!("dev_mode native is only supported for RustBinary and TrunkWasm components");
+        }
+
+        if yaml_section.get("https_port").is_some() && !matches!(build_type, BuildType::Ingress { .. }) {
+            panic!("https_port is only supported for Ingress components");
+        }
+        if yaml_section.get("ingress_mode").is_some() && !matches!(build_type, BuildType::Ingress { .. }) {
+            panic!("ingress_mode is only supported for Ingress components");
+        }
+        if ingress_mode.is_builtin() && yaml_section.get("https_port").is_some() {
+            panic!("https_port is not supported with ingress_mode builtin");
+        }
+
         let cwd = std::env::current_dir()
             .expect("Failed to get current working directory")
             .to_str()
@@ -226,30 +324,58 @@ impl ComponentBuildSpec {
                      .map(|item| Self::process_template_string(item.as_str().unwrap(), &variables))
                      .collect()
                 }),                
-            env: yaml_section.get("env").map(|v| {
-                v.as_mapping()
-                    .unwrap()
-                    .iter()
-                    .map(|(k, val)| {
-                        let v = Self::process_template_string(val.as_str().unwrap(), &variables);
-                        (
-                            Self::process_template_string(k.as_str().unwrap(), &variables),
-                            v,
-                        )
-                    })
-                    .collect()
-            }),
+            env: {
+                let inline: Option<HashMap<String, String>> = yaml_section.get("env").map(|v| {
+                    v.as_mapping()
+                        .unwrap()
+                        .iter()
+                        .map(|(k, val)| {
+                            let v = Self::process_template_string(val.as_str().unwrap(), &variables);
+                            (
+                                Self::process_template_string(k.as_str().unwrap(), &variables),
+                                v,
+                            )
+                        })
+                        .collect()
+                });
+                // Values written inline in the stack override those from env files
+                match yaml_section.get("env_file").map(|v| load_env_files(&config, v)) {
+                    Some(mut env) => {
+                        env.extend(inline.unwrap_or_default());
+                        Some(env)
+                    }
+                    None => inline,
+                }
+            },
+            secret_env: yaml_section
+                .get("secret_env")
+                .map_or(Vec::new(), |v| {
+                    v.as_sequence()
+                        .unwrap_or_else(|| panic!("secret_env must be a list of variable names"))
+                        .iter()
+                        .map(|name| name.as_str().expect("secret_env entries must be variable names").to_string())
+                        .collect()
+                }),
             volumes: yaml_section.get("volumes").map(|v| {
                 v.as_mapping()
                     .unwrap()
                     .iter()
                     .map(|(k, val)| {
-                        let absolute_path = std::path::Path::new(&cwd)
-                            .join(Self::process_template_string(k.as_str().unwrap(), &variables))
-                            .to_str()
-                            .unwrap()
-                            .to_string();
-                        (absolute_path, Self::process_template_string(val.as_str().unwrap(), &variables))
+                        let source = Self::process_template_string(k.as_str().unwrap(), &variables);
+                        // A named volume is owned by the product
+                        let source = if let Some(name) = source.strip_prefix(VOLUME_PREFIX) {
+                            if name.is_empty() || name.contains('/') {
+                                panic!("Invalid volume name: {}", name);
+                            }
+                            format!("{}-{}", config.product_uri(), name)
+                        } else {
+                            std::path::Path::new(&cwd)
+                                .join(source)
+                                .to_str()
+                                .unwrap()
+                                .to_string()
+                        };
+                        (source, Self::process_template_string(val.as_str().unwrap(), &variables))
                     })
                     .collect()
             }),
@@ -270,12 +396,48 @@ impl ComponentBuildSpec {
                     v.as_u64().unwrap() as u16
                 }
             }),
+            https_port: yaml_section.get("https_port").map(|v| {
+                if let Some(port_str) = v.as_str() {
+                    let processed_str = Self::process_template_string(port_str, &variables);
+                    processed_str.parse::<u16>().unwrap_or_else(|_| panic!("Could not parse {}", processed_str))
+                } else {
+                    v.as_u64().unwrap() as u16
+                }
+            }),
             k8s: yaml_section
                 .get("k8s")
                 .map(|v| Self::process_template_string(v.as_str().unwrap(), &variables)),
             priority: yaml_section
                 .get("priority")
                 .map_or(100, |v| v.as_u64().unwrap()),
+            log_format: yaml_section
+                .get("log_format")
+                .map_or(LogFormat::default(), |v| {
+                    let format = Self::process_template_string(v.as_str().unwrap(), &variables);
+                    LogFormat::parse(&format).unwrap_or_else(|| panic!("Invalid log_format: {}", format))
+                }),
+            dev_mode,
+            ingress_mode,
+            stop_signal: yaml_section
+                .get("stop_signal")
+                .map_or(DEFAULT_STOP_SIGNAL.to_string(), |v| {
+                    let signal = Self::process_template_string(v.as_str().unwrap(), &variables).to_uppercase();
+                    let signal = signal.trim_start_matches("SIG").to_string();
+                    if signal.is_empty() || !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+                        panic!("Invalid stop_signal: {}", signal);
+                    }
+                    signal
+                }),
+            stop_timeout: yaml_section
+                .get("stop_timeout")
+                .map_or(DEFAULT_STOP_TIMEOUT, |v| {
+                    v.as_u64().unwrap_or_else(|| panic!("stop_timeout must be a number of seconds"))
+                }),
+            resources: yaml_section
+                .get("resources")
+                .map_or(Resources::default(), |v| {
+                    Resources::from_yaml(v, config.environment()).unwrap_or_else(|e| panic!("Invalid resources: {}", e))
+                }),
             config,
             variables,
             services: None,
@@ -284,14 +446,34 @@ impl ComponentBuildSpec {
     }
 
     fn process_template_string(input: &str, variables: &Arc<Variables>) -> String {
+        Self::try_process_template_string(input, variables).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Replaces a value that is a single `{{ variable }}` with its value.
+    pub fn try_process_template_string(input: &str, variables: &Variables) -> Result<String, String> {
         if input.starts_with("{{") && input.ends_with("}}") {
             let var_name = input.trim_start_matches("{{").trim_end_matches("}}").trim();
-            variables.get(var_name).expect(&format!("Variable `{}` not found", var_name)).to_string()
+            // References to other components are resolved once all are loaded
+            if References::is_reference(var_name) {
+                return Ok(input.to_string());
+            }
+            variables.get(var_name).ok_or(format!("Variable `{}` not found", var_name))
         } else {
-            input.to_string()
+            Ok(input.to_string())
         }
     }
 
+    /// Whether the component serves HTTPS with the development certificates,
+    /// which are only ever used in dev
+    pub fn serves_dev_tls(&self) -> bool {
+        self.https_port.is_some() && self.config.environment() == "dev"
+    }
+
+    /// Whether the component is served by rushd's own proxy in dev
+    pub fn is_builtin_ingress(&self) -> bool {
+        matches!(self.build_type, BuildType::Ingress { .. }) && self.ingress_mode.is_builtin()
+    }
+
     pub fn build_script(&self, ctx: &BuildContext) -> String {
         match &self.build {
             Some(build) => build.clone(),
@@ -319,6 +501,20 @@ impl ComponentBuildSpec {
             .services
             .clone()
             .expect("No services found for docker image");
+        let namespace = format!("{}-{}", self.config.product_uri(), self.config.environment());
+        // Only components with manifests have a Service to be reached at, and
+        // the component's own env takes precedence
+        let cluster_services = services
+            .iter()
+            .filter(|(_, service)| service.cluster_port.is_some())
+            .map(|(name, service)| (name.clone(), service.clone()))
+            .collect::<ServicesSpec>();
+        let mut discovery = discovery_env(&cluster_services, |service| {
+            (format!("{}-service.{}.svc.cluster.local", service.name, namespace), service.cluster_port.unwrap_or_default())
+        });
+        let env = self.env.clone().unwrap_or_default();
+        discovery.retain(|name, _| !env.contains_key(name));
+        let cluster_port = services.get(&self.component_name).and_then(|service| service.cluster_port);
         let (location, services) = match &self.build_type {
             BuildType::TrunkWasm { location, .. } => (Some(location.clone()), None),
             BuildType::RustBinary { location, .. } => (Some(location.clone()), None),
@@ -341,6 +537,14 @@ impl ComponentBuildSpec {
         let product_name = self.product_name.clone();
         let product_uri = slug::slugify(&product_name);
 
+        let services = services.unwrap_or_default();
+        let mut subdomains = services
+            .values()
+            .filter_map(|service| service.subdomain.clone())
+            .collect::<Vec<_>>();
+        subdomains.sort();
+        subdomains.dedup();
+
         BuildContext {
             toolchain: (*toolchain).clone(),
             build_type: self.build_type.clone(),
@@ -348,7 +552,19 @@ impl ComponentBuildSpec {
             target: toolchain.target().clone(),
             host: toolchain.host().clone(),
             rust_target: toolchain.target().to_rust_target(),
-            services: services.unwrap_or_default(),
+            services,
+            discovery,
+            cluster_port,
+            env: self.env
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(name, _)| !self.secret_env.contains(name))
+                .collect(),
+            secret_env: self.secret_env.clone(),
+            resources: self.resources.clone(),
+            subdomains,
+            tls: self.serves_dev_tls(),
             environment: self.config.environment().to_string(),
             domain: self.config.domain().to_string(),
             product_name: product_name,
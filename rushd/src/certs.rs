@@ -0,0 +1,169 @@
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const CA_NAME: &str = "rushd-dev-ca";
+const TRUST_MARKER: &str = ".trust-instructions-shown";
+
+/// Certificates are reissued when they expire within this many seconds
+const RENEW_BEFORE: u64 = 30 * 24 * 60 * 60;
+
+/// A certificate for the dev ingress, signed by a local development CA. The
+/// CA is shared by all products in the repository, so it only has to be
+/// trusted once.
+pub struct DevCertificates {
+    ca_certificate: PathBuf,
+    certificate: PathBuf,
+    key: PathBuf,
+}
+
+fn openssl(args: &[&str]) -> Result<(), String> {
+    let output = Command::new("openssl")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run openssl, is it installed? {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "openssl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// Whether the certificate exists and stays valid for `RENEW_BEFORE`.
+fn is_current(certificate: &Path) -> bool {
+    certificate.exists()
+        && Command::new("openssl")
+            .args(["x509", "-noout", "-checkend", &RENEW_BEFORE.to_string(), "-in"])
+            .arg(certificate)
+            .output()
+            .is_ok_and(|output| output.status.success())
+}
+
+impl DevCertificates {
+    /// Directory of the product's certificate and key
+    pub fn directory(product_path: &Path) -> PathBuf {
+        product_path.join("target").join("rushd").join("certs")
+    }
+
+    /// Creates the CA under `<root>/target/rushd/ca` and a certificate for
+    /// `hosts` under `<product>/target/rushd/certs`, reusing both until they
+    /// are about to expire, and the certificate while it covers the same
+    /// hosts.
+    pub fn ensure(root_path: &Path, product_path: &Path, hosts: &[String]) -> Result<Self, String> {
+        let ca_dir = root_path.join("target").join("rushd").join("ca");
+        let certs_dir = Self::directory(product_path);
+        std::fs::create_dir_all(&ca_dir).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&certs_dir).map_err(|e| e.to_string())?;
+
+        let ca_certificate = ca_dir.join(format!("{}.crt", CA_NAME));
+        let ca_key = ca_dir.join(format!("{}.key", CA_NAME));
+        let new_ca = !is_current(&ca_certificate) || !ca_key.exists();
+        if new_ca {
+            println!("{}", "Creating local development CA".white().bold());
+            // The new CA has to be trusted again
+            let _ = std::fs::remove_file(ca_dir.join(TRUST_MARKER));
+            openssl(&[
+                "req", "-x509", "-new", "-nodes",
+                "-newkey", "rsa:2048",
+                "-keyout", &ca_key.display().to_string(),
+                "-out", &ca_certificate.display().to_string(),
+                "-days", "3650",
+                "-subj", "/CN=rushd development CA",
+                "-addext", "basicConstraints=critical,CA:TRUE",
+                "-addext", "keyUsage=critical,keyCertSign,cRLSign",
+            ])?;
+        }
+
+        let ret = DevCertificates {
+            ca_certificate,
+            certificate: certs_dir.join("tls.crt"),
+            key: certs_dir.join("tls.key"),
+        };
+
+        let hosts_file = certs_dir.join("hosts");
+        let hosts_list = hosts.join("\n");
+        let up_to_date = !new_ca
+            && is_current(&ret.certificate)
+            && ret.key.exists()
+            && std::fs::read_to_string(&hosts_file).is_ok_and(|content| content == hosts_list);
+
+        if !up_to_date {
+            ret.create_certificate(&ca_key, &certs_dir, hosts)?;
+            std::fs::write(&hosts_file, hosts_list).map_err(|e| e.to_string())?;
+        }
+
+        ret.print_trust_instructions(&ca_dir);
+        Ok(ret)
+    }
+
+    fn create_certificate(&self, ca_key: &Path, certs_dir: &Path, hosts: &[String]) -> Result<(), String> {
+        let csr = certs_dir.join("tls.csr");
+        let extensions = certs_dir.join("tls.ext");
+        let subject_alt_names = hosts
+            .iter()
+            .map(|host| match host.parse::<std::net::IpAddr>() {
+                Ok(_) => format!("IP:{}", host),
+                Err(_) => format!("DNS:{}", host),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        std::fs::write(
+            &extensions,
+            format!(
+                "basicConstraints=CA:FALSE\nkeyUsage=digitalSignature,keyEncipherment\nextendedKeyUsage=serverAuth\nsubjectAltName={}\n",
+                subject_alt_names
+            ),
+        )
+        .map_err(|e| e.to_string())?;
+
+        openssl(&[
+            "req", "-new", "-nodes",
+            "-newkey", "rsa:2048",
+            "-keyout", &self.key.display().to_string(),
+            "-out", &csr.display().to_string(),
+            "-subj", &format!("/CN={}", hosts.first().map(|s| s.as_str()).unwrap_or("localhost")),
+        ])?;
+
+        // Browsers reject server certificates valid for more than 397 days
+        openssl(&[
+            "x509", "-req",
+            "-in", &csr.display().to_string(),
+            "-CA", &self.ca_certificate.display().to_string(),
+            "-CAkey", &ca_key.display().to_string(),
+            "-CAcreateserial",
+            "-out", &self.certificate.display().to_string(),
+            "-days", "397",
+            "-sha256",
+            "-extfile", &extensions.display().to_string(),
+        ])?;
+
+        let _ = std::fs::remove_file(&csr);
+        Ok(())
+    }
+
+    fn print_trust_instructions(&self, ca_dir: &Path) {
+        let marker = ca_dir.join(TRUST_MARKER);
+        if marker.exists() {
+            return;
+        }
+
+        let ca = self.ca_certificate.display();
+        println!("{}", "A local development CA was created for HTTPS in the dev ingress.".white().bold());
+        println!("Trust it once to avoid certificate warnings in your browser:");
+        if cfg!(target_os = "macos") {
+            println!("    sudo security add-trusted-cert -d -r trustRoot -k /Library/Keychains/System.keychain {}", ca);
+        } else {
+            println!("    sudo cp {} /usr/local/share/ca-certificates/{}.crt", ca, CA_NAME);
+            println!("    sudo update-ca-certificates");
+        }
+        println!("Firefox keeps its own certificate store: import {} under Settings > Privacy & Security > Certificates.", ca);
+        println!();
+
+        let _ = std::fs::write(marker, "");
+    }
+}
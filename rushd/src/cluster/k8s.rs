@@ -1,14 +1,57 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::builder::Artefact;
+use crate::gitignore::Pattern;
 use std::sync::Arc;
 use crate::toolchain::ToolchainContext;
-use crate::cluster::run_command;
+use crate::utils::run_command;
 use colored::Colorize;
 use crate::builder::BuildContext;
 use std::sync::Mutex;
 use crate::builder::ComponentBuildSpec;
 use crate::builder::BuildType;
 
+/// Extensions of the files in a `k8s` directory that are rendered as
+/// manifests, the same that `kubectl apply -R` picks up.
+const MANIFEST_EXTENSIONS: [&str; 3] = ["yaml", "yml", "json"];
+
+/// Lists files in a `k8s` directory that are not manifests, in the syntax
+/// of `.gitignore` and relative to that directory.
+const IGNORE_FILE: &str = ".rushdignore";
+
+pub fn is_manifest(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| MANIFEST_EXTENSIONS.contains(&extension))
+}
+
+/// Manifests below `directory`, relative to `root`, leaving out files and
+/// directories matched by `ignore`.
+fn find_manifests(root: &Path, directory: &Path, ignore: &[Pattern], manifests: &mut Vec<PathBuf>) {
+    let entries = std::fs::read_dir(directory)
+        .unwrap_or_else(|_| panic!("Failed to read input directory: {}", directory.display()))
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path());
+
+    for path in entries {
+        let relative_path = path.strip_prefix(root).expect("Manifest outside of its directory").to_path_buf();
+        let is_dir = path.is_dir();
+        let ignored = ignore
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(&relative_path.display().to_string(), is_dir))
+            .is_some_and(|pattern| !pattern.is_negated());
+        if ignored {
+            continue;
+        }
+
+        if is_dir {
+            find_manifests(root, &path, ignore, manifests);
+        } else if is_manifest(&path) {
+            manifests.push(relative_path);
+        }
+    }
+}
+
 pub struct K8ClusterManifests {
     components: Vec<K8ComponentManifests>,
     toolchain: Option<Arc<ToolchainContext>>,
@@ -63,19 +106,19 @@ impl K8ComponentManifests {
             namespace,
         };
 
-        let paths = std::fs::read_dir(&input_directory)
-            .expect(&format!("Failed to read input directory: {}", input_directory.display()))
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.path())
-            .filter(|path| path.is_dir() || path.extension().map_or(false, |ext| ext == "yaml"))
-            .collect::<Vec<_>>();
+        let ignore = std::fs::read_to_string(input_directory.join(IGNORE_FILE))
+            .map(|content| content.lines().filter_map(Pattern::new).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut paths = Vec::new();
+        find_manifests(&input_directory, &input_directory, &ignore, &mut paths);
+        paths.sort();
 
+        // Subdirectories are kept in the output
         for path in paths {
-            if !path.is_dir() {
-                let output_path = output_directory.join(path.file_name().unwrap());
-                let artefact = Artefact::new(path.clone().display().to_string(), output_path.display().to_string());
-                ret.manifests.push(artefact);
-            }
+            let input_path = input_directory.join(&path);
+            let output_path = output_directory.join(&path);
+            let artefact = Artefact::new(input_path.display().to_string(), output_path.display().to_string());
+            ret.manifests.push(artefact);
         }
 
         ret
@@ -0,0 +1,120 @@
+use crate::cluster::LocalCluster;
+use crate::toolchain::ToolchainContext;
+use crate::utils::run_command;
+use colored::Colorize;
+use futures::future::BoxFuture;
+use std::process::Command;
+use std::sync::Arc;
+
+/// Name of the cluster rushd creates, the default of `kind create cluster`
+const CLUSTER_NAME: &str = "kind";
+
+/// Maps the ports of the cluster's ingress to the host, as in kind's
+/// ingress guide, so the product is reachable under 127.0.0.1.
+const CLUSTER_CONFIG: &str = r#"kind: Cluster
+apiVersion: kind.x-k8s.io/v1alpha4
+nodes:
+  - role: control-plane
+    kubeadmConfigPatches:
+      - |
+        kind: InitConfiguration
+        nodeRegistration:
+          kubeletExtraArgs:
+            node-labels: "ingress-ready=true"
+    extraPortMappings:
+      - containerPort: 80
+        hostPort: 80
+      - containerPort: 443
+        hostPort: 443
+"#;
+
+/// A kind cluster. Its only node is a Docker container, which is stopped
+/// and started again rather than recreated.
+pub struct Kind {
+    toolchain: Arc<ToolchainContext>,
+}
+
+impl Kind {
+    pub fn new(toolchain: Arc<ToolchainContext>) -> Self {
+        Kind { toolchain }
+    }
+
+    fn executable(&self) -> Result<String, String> {
+        self.toolchain.kind().ok_or_else(|| "kind executable not found. Please install it.".to_string())
+    }
+
+    fn node_container(&self) -> String {
+        format!("{}-control-plane", CLUSTER_NAME)
+    }
+
+    fn exists(&self) -> Result<bool, String> {
+        let output = Command::new(self.executable()?)
+            .args(["get", "clusters"])
+            .output()
+            .map_err(|e| format!("Failed to list kind clusters: {}", e))?;
+        Ok(String::from_utf8_lossy(&output.stdout).lines().any(|cluster| cluster.trim() == CLUSTER_NAME))
+    }
+
+    async fn create(&self) -> Result<(), String> {
+        let config_path = std::env::temp_dir().join("rushd-kind.yaml");
+        std::fs::write(&config_path, CLUSTER_CONFIG)
+            .map_err(|e| format!("Failed to write {}: {}", config_path.display(), e))?;
+        let config_path = config_path.display().to_string();
+        run_command(
+            "kind".white().bold(),
+            &self.executable()?,
+            vec!["create", "cluster", "--name", CLUSTER_NAME, "--config", &config_path],
+        )
+        .await
+    }
+
+    async fn start_cluster(&self) -> Result<(), String> {
+        if !self.exists()? {
+            return self.create().await;
+        }
+        let node = self.node_container();
+        run_command("kind".white().bold(), self.toolchain.docker(), vec!["start", &node]).await
+    }
+}
+
+impl LocalCluster for Kind {
+    fn name(&self) -> &str {
+        "kind"
+    }
+
+    fn context(&self) -> String {
+        format!("kind-{}", CLUSTER_NAME)
+    }
+
+    fn start(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.start_cluster())
+    }
+
+    fn stop(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            let node = self.node_container();
+            run_command("kind".white().bold(), self.toolchain.docker(), vec!["stop", &node]).await
+        })
+    }
+
+    fn delete(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            run_command("kind".white().bold(), &self.executable()?, vec!["delete", "cluster", "--name", CLUSTER_NAME]).await
+        })
+    }
+
+    fn load_image<'a>(&'a self, image: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            run_command(
+                "kind".white().bold(),
+                &self.executable()?,
+                vec!["load", "docker-image", image, "--name", CLUSTER_NAME],
+            )
+            .await
+        })
+    }
+
+    fn ingress_address(&self) -> BoxFuture<'_, Result<String, String>> {
+        Box::pin(async move { Ok("127.0.0.1".to_string()) })
+    }
+}
@@ -0,0 +1,37 @@
+use crate::cluster::{Kind, Minikube};
+use crate::toolchain::ToolchainContext;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+/// A Kubernetes cluster running on the developer's machine, which
+/// `rushd <product> cluster dev` deploys to without a registry.
+pub trait LocalCluster {
+    /// Name of the tool, as written in rushd.yaml
+    fn name(&self) -> &str;
+
+    /// kubectl context of the cluster
+    fn context(&self) -> String;
+
+    fn start(&self) -> BoxFuture<'_, Result<(), String>>;
+
+    fn stop(&self) -> BoxFuture<'_, Result<(), String>>;
+
+    fn delete(&self) -> BoxFuture<'_, Result<(), String>>;
+
+    /// Copies an image from the local Docker daemon into the cluster, so
+    /// that pods can run it without a registry.
+    fn load_image<'a>(&'a self, image: &'a str) -> BoxFuture<'a, Result<(), String>>;
+
+    /// Address at which the cluster's ingress is reachable from the host
+    fn ingress_address(&self) -> BoxFuture<'_, Result<String, String>>;
+}
+
+/// The cluster selected with `local_cluster` in rushd.yaml, minikube if
+/// it is not set.
+pub fn local_cluster(name: Option<&str>, toolchain: Arc<ToolchainContext>) -> Result<Box<dyn LocalCluster>, String> {
+    match name.unwrap_or("minikube") {
+        "minikube" => Ok(Box::new(Minikube::new(toolchain))),
+        "kind" => Ok(Box::new(Kind::new(toolchain))),
+        name => Err(format!("Unknown local_cluster `{}`, expected minikube or kind", name)),
+    }
+}
@@ -0,0 +1,74 @@
+use crate::cluster::LocalCluster;
+use crate::toolchain::ToolchainContext;
+use crate::utils::run_command;
+use colored::Colorize;
+use futures::future::BoxFuture;
+use std::process::Command;
+use std::sync::Arc;
+
+pub struct Minikube {
+    toolchain: Arc<ToolchainContext>,
+}
+
+impl Minikube {
+    pub fn new(toolchain: Arc<ToolchainContext>) -> Self {
+
+        Minikube {
+            toolchain,
+        }
+    }
+
+    fn executable(&self) -> Result<String, String> {
+        self.toolchain.minikube().ok_or_else(|| "Minikube executable not found. Please install it.".to_string())
+    }
+
+    async fn run(&self, args: Vec<&str>) -> Result<(), String> {
+        run_command("minikube".white().bold(), &self.executable()?, args).await
+    }
+
+    pub async fn get_ip(&self) -> Result<String, String> {
+        let output = Command::new(self.executable()?)
+            .arg("ip")
+            .output()
+            .expect("Failed to get minikube IP");
+
+        if !output.status.success() {
+            Err(format!(
+                "Failed to get minikube IP: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        } else {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+    }
+}
+
+impl LocalCluster for Minikube {
+    fn name(&self) -> &str {
+        "minikube"
+    }
+
+    fn context(&self) -> String {
+        "minikube".to_string()
+    }
+
+    fn start(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.run(vec!["start"]))
+    }
+
+    fn stop(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.run(vec!["stop"]))
+    }
+
+    fn delete(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.run(vec!["delete"]))
+    }
+
+    fn load_image<'a>(&'a self, image: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(self.run(vec!["image", "load", image]))
+    }
+
+    fn ingress_address(&self) -> BoxFuture<'_, Result<String, String>> {
+        Box::pin(self.get_ip())
+    }
+}
@@ -0,0 +1,186 @@
+use crate::builder::{BuildType, Config};
+use crate::container::docker::{DockerImage, COMPONENT_LABEL, ENVIRONMENT_LABEL, PRODUCT_LABEL};
+use crate::container::migrations::{Migrations, MIGRATIONS_KEY};
+use crate::container::volumes::{Mount, VolumeSource};
+use crate::container::{discovery_env, ServicesSpec};
+use crate::container::container_reactor::NATIVE_HOST;
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Compose interpolates `$VAR` in values, which would change environment
+/// variables and scripts that contain a dollar sign.
+pub fn escape(value: &str) -> String {
+    value.replace('$', "$$")
+}
+
+/// Compose files are assembled as YAML mappings, which keep their keys in
+/// the order they are set rather than sorted.
+pub fn set<T: Serialize>(mapping: &mut Mapping, key: &str, value: T) {
+    let value = serde_yaml::to_value(value).expect("Failed to convert to YAML");
+    mapping.insert(Value::String(key.to_string()), value);
+}
+
+/// Host paths inside the directory of the compose file are written relative
+/// to it, so the file keeps working in another checkout.
+fn host_path(path: &Path, output_directory: &Path) -> String {
+    match path.strip_prefix(output_directory) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => format!("./{}", relative.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+/// Images built by rushd are referenced in the registry they are pushed to.
+fn image_reference(image: &DockerImage, config: &Config) -> String {
+    match image.spec().build_type {
+        BuildType::PureDockerImage { .. } => image.identifier(),
+        _ if config.docker_registry().is_empty() => image.tagged_image_name(),
+        _ => format!("{}/{}", config.docker_registry(), image.tagged_image_name()),
+    }
+}
+
+/// Renders the dev stack as a Docker Compose file: the same images, ports,
+/// environment, volumes and network as `rushd dev`. Components that only
+/// exist in Kubernetes and the built-in ingress are left out.
+pub fn render(
+    config: &Config,
+    images: &[DockerImage],
+    services: &ServicesSpec,
+    migrations: Option<&Migrations>,
+    product_directory: &Path,
+    output_directory: &Path,
+) -> Result<String, String> {
+    let network_name = config.network_name().to_string();
+    let exported = images
+        .iter()
+        .filter(|image| {
+            let spec = image.spec();
+            if spec.is_builtin_ingress() {
+                eprintln!(
+                    "{} {} uses the built-in ingress, which needs rushd and is not exported",
+                    "Warning:".yellow().bold(),
+                    spec.component_name
+                );
+                return false;
+            }
+            !matches!(
+                spec.build_type,
+                BuildType::PureKubernetes | BuildType::KubernetesInstallation { .. } | BuildType::ApiDocumentation { .. }
+            )
+        })
+        .collect::<Vec<_>>();
+    let exported_names = exported.iter().map(|image| image.component_name()).collect::<Vec<_>>();
+
+    let mut compose_services = Mapping::new();
+    let mut compose_volumes = Mapping::new();
+    for image in &exported {
+        let spec = image.spec();
+        let mut service = Mapping::new();
+        set(&mut service, "image", json!(image_reference(image, config)));
+        set(&mut service, "container_name", json!(image.container_name()));
+
+        if let BuildType::PureDockerImage { command, entrypoint, .. } = &spec.build_type {
+            if let Some(entrypoint) = entrypoint {
+                set(&mut service, "entrypoint", json!(entrypoint));
+            }
+            if let Some(command) = command {
+                set(&mut service, "command", json!(command));
+            }
+        }
+
+        let mut ports = Vec::new();
+        if let Some(service_spec) = services.get(&spec.component_name) {
+            ports.push(format!("{}:{}", service_spec.port, service_spec.target_port));
+        }
+        if let Some(https_port) = spec.https_port {
+            ports.push(format!("{}:443", https_port));
+        }
+        if !ports.is_empty() {
+            set(&mut service, "ports", json!(ports));
+        }
+
+        // Every exported component runs in a container, reachable under its
+        // name. The component's own env takes precedence, as in `rushd dev`.
+        let mut environment = discovery_env(services, |service| (service.name.clone(), service.target_port));
+        environment.extend(spec.env.clone().unwrap_or_default());
+        if !environment.is_empty() {
+            let environment = environment.iter().map(|(k, v)| (k.clone(), escape(v))).collect::<BTreeMap<_, _>>();
+            set(&mut service, "environment", json!(environment));
+        }
+
+        let mut volumes = Vec::new();
+        for mount in Mount::from_images(images, Some(&spec.component_name))? {
+            let source = match &mount.source {
+                VolumeSource::Named(name) => {
+                    set(&mut compose_volumes, name, json!({ "name": name }));
+                    name.clone()
+                }
+                VolumeSource::Bind(path) => host_path(path, output_directory),
+            };
+            volumes.push(format!("{}:{}", source, mount.container_path));
+        }
+        for (host, container_path) in image.dev_certificate_mounts() {
+            volumes.push(format!("{}:{}:ro", host_path(&host, output_directory), container_path));
+        }
+        if !volumes.is_empty() {
+            set(&mut service, "volumes", json!(volumes));
+        }
+
+        let depends_on = spec
+            .depends_on
+            .iter()
+            .filter(|dependency| exported_names.contains(dependency))
+            .collect::<Vec<_>>();
+        if !depends_on.is_empty() {
+            set(&mut service, "depends_on", json!(depends_on));
+        }
+
+        set(&mut service, "networks", json!({ &network_name: { "aliases": [spec.component_name] } }));
+        set(&mut service, "extra_hosts", json!([format!("{}:host-gateway", NATIVE_HOST)]));
+        set(&mut service, "stop_signal", json!(format!("SIG{}", spec.stop_signal)));
+        set(&mut service, "stop_grace_period", json!(format!("{}s", spec.stop_timeout)));
+        if let Some(cpus) = spec.resources.cpus() {
+            set(&mut service, "cpus", json!(cpus));
+        }
+        if let Some(memory) = spec.resources.memory_bytes() {
+            set(&mut service, "mem_limit", json!(memory));
+        }
+        set(
+            &mut service,
+            "labels",
+            json!(BTreeMap::from([
+                (PRODUCT_LABEL, config.product_name().to_string()),
+                (ENVIRONMENT_LABEL, config.environment().to_string()),
+                (COMPONENT_LABEL, spec.component_name.clone()),
+            ])),
+        );
+
+        set(&mut compose_services, &spec.component_name, service);
+    }
+
+    if let Some(migrations) = migrations {
+        let directory = host_path(&product_directory.join(migrations.directory()), output_directory);
+        let service = migrations.compose_service(&directory, &network_name, images)?;
+        set(&mut compose_services, MIGRATIONS_KEY, service);
+    }
+
+    let mut compose = Mapping::new();
+    set(&mut compose, "name", json!(format!("{}-{}", config.product_uri(), config.environment())));
+    set(&mut compose, "services", compose_services);
+    set(&mut compose, "networks", json!({ &network_name: { "name": network_name } }));
+    if !compose_volumes.is_empty() {
+        set(&mut compose, "volumes", compose_volumes);
+    }
+
+    let yaml = serde_yaml::to_string(&compose).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "# Generated by `rushd {} export compose`. Images built by rushd are\n# published with `rushd {} push`.\n{}",
+        config.product_name(),
+        config.product_name(),
+        yaml
+    ))
+}
@@ -0,0 +1,439 @@
+use crate::builder::{Resources, VOLUME_PREFIX};
+use crate::container::compose;
+use serde_yaml::{Mapping, Value};
+use std::path::{Component, Path, PathBuf};
+
+/// Colors given to the imported components in turn
+const COLORS: [&str; 6] = ["blue", "green", "yellow", "purple", "cyan", "magenta"];
+
+/// A stack.yaml translated from a compose file, together with the compose
+/// features that have no counterpart in the stack.
+pub struct ComposeImport {
+    pub stack: String,
+    pub untranslated: Vec<String>,
+}
+
+/// Removes `.` and `..` from a path without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The path of `to` relative to the directory `from`. Both are absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Parses a compose duration such as `30s` or `1m30s` into seconds.
+fn parse_duration(duration: &str) -> Option<u64> {
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in duration.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value = number.parse::<u64>().ok()?;
+        number.clear();
+        seconds += match c {
+            'h' => value * 3600,
+            'm' => value * 60,
+            's' => value,
+            _ => return None,
+        };
+    }
+    if !number.is_empty() {
+        seconds += number.parse::<u64>().ok()?;
+    }
+    Some(seconds)
+}
+
+/// Docker writes memory as bytes or with a `b`, `k`, `m` or `g` suffix for
+/// binary multiples, which Kubernetes writes as `Ki`, `Mi` and `Gi`.
+fn memory_quantity(memory: &str) -> String {
+    let memory = memory.trim();
+    match memory.char_indices().last() {
+        Some((i, 'k' | 'K')) => format!("{}Ki", &memory[..i]),
+        Some((i, 'm' | 'M')) => format!("{}Mi", &memory[..i]),
+        Some((i, 'g' | 'G')) => format!("{}Gi", &memory[..i]),
+        Some((i, 'b' | 'B')) => memory[..i].to_string(),
+        _ => memory.to_string(),
+    }
+}
+
+struct Translator<'a> {
+    compose_directory: &'a Path,
+    product_directory: &'a Path,
+    untranslated: Vec<String>,
+}
+
+impl Translator<'_> {
+    fn report(&mut self, path: String, reason: &str) {
+        self.untranslated.push(format!("{}: {}", path, reason));
+    }
+
+    /// A path of the compose file, which is relative to the compose file,
+    /// as a path relative to the product directory.
+    fn stack_path(&self, path: &Path) -> String {
+        let path = normalize(&self.compose_directory.join(path));
+        let relative = relative_path(self.product_directory, &path);
+        if relative.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            relative.display().to_string()
+        }
+    }
+
+    fn translate_build(&mut self, name: &str, build: &Value, component: &mut Mapping) {
+        let (context, dockerfile) = match build {
+            Value::Mapping(build) => {
+                for key in build.keys().filter_map(|key| key.as_str()) {
+                    if key != "context" && key != "dockerfile" {
+                        self.report(format!("services.{}.build.{}", name, key), "not supported by rushd builds");
+                    }
+                }
+                let context = build.get("context").and_then(scalar).unwrap_or(".".to_string());
+                let dockerfile = build.get("dockerfile").and_then(scalar).unwrap_or("Dockerfile".to_string());
+                (context, dockerfile)
+            }
+            build => (scalar(build).unwrap_or(".".to_string()), "Dockerfile".to_string()),
+        };
+
+        let context = normalize(&self.compose_directory.join(context));
+        let dockerfile = normalize(&context.join(dockerfile));
+        // Docker builds run from the directory of the Dockerfile
+        let context_dir = relative_path(dockerfile.parent().unwrap_or(&context), &context);
+        let context_dir = if context_dir.as_os_str().is_empty() { ".".to_string() } else { context_dir.display().to_string() };
+
+        let build_type = if context.join("Cargo.toml").exists() { "RustBinary" } else { "Script" };
+        compose::set(component, "build_type", build_type);
+        compose::set(component, "location", self.stack_path(&context));
+        compose::set(component, "dockerfile", self.stack_path(&dockerfile));
+        if context_dir != "." {
+            compose::set(component, "context_dir", context_dir);
+        }
+    }
+
+    /// Image components pass `command` and `entrypoint` to `docker run` as a
+    /// single argument.
+    fn translate_argument(&mut self, name: &str, key: &str, value: &Value, component: &mut Mapping) {
+        let argument = match value {
+            Value::Sequence(arguments) if arguments.len() == 1 => scalar(&arguments[0]),
+            Value::Sequence(_) => None,
+            value => scalar(value).filter(|argument| !argument.contains(' ')),
+        };
+        match argument {
+            Some(argument) => compose::set(component, key, argument),
+            None => self.report(format!("services.{}.{}", name, key), "only a single argument is supported"),
+        }
+    }
+
+    fn translate_ports(&mut self, name: &str, ports: &Value, component: &mut Mapping) {
+        let ports = ports.as_sequence().cloned().unwrap_or_default();
+        for (index, port) in ports.iter().enumerate() {
+            if index > 0 {
+                self.report(format!("services.{}.ports[{}]", name, index), "components publish a single port");
+                continue;
+            }
+
+            let (published, target) = match port {
+                Value::Mapping(port) => (
+                    port.get("published").and_then(scalar),
+                    port.get("target").and_then(scalar),
+                ),
+                port => {
+                    let port = scalar(port).unwrap_or_default();
+                    if port.ends_with("/udp") {
+                        self.report(format!("services.{}.ports[{}]", name, index), "UDP ports are not supported");
+                        continue;
+                    }
+                    let parts = port.trim_end_matches("/tcp").split(':').map(|p| p.to_string()).collect::<Vec<_>>();
+                    match parts.len() {
+                        1 => (None, Some(parts[0].clone())),
+                        _ => (Some(parts[parts.len() - 2].clone()), Some(parts[parts.len() - 1].clone())),
+                    }
+                }
+            };
+
+            let target = target.and_then(|target| target.parse::<u16>().ok());
+            let published = match published.map(|published| published.parse::<u16>()) {
+                Some(Ok(published)) => Some(published),
+                Some(Err(_)) => {
+                    self.report(format!("services.{}.ports[{}]", name, index), "port ranges are not supported");
+                    continue;
+                }
+                None => None,
+            };
+            match (published, target) {
+                (Some(published), Some(target)) => {
+                    compose::set(component, "port", published);
+                    compose::set(component, "target_port", target);
+                }
+                (None, Some(target)) => {
+                    self.report(format!("services.{}.ports[{}]", name, index), "published on the same port, rushd needs a fixed host port");
+                    compose::set(component, "port", target);
+                    compose::set(component, "target_port", target);
+                }
+                _ => self.report(format!("services.{}.ports[{}]", name, index), "port ranges are not supported"),
+            }
+        }
+    }
+
+    fn translate_environment(&mut self, name: &str, environment: &Value, component: &mut Mapping) {
+        let variables = match environment {
+            Value::Mapping(variables) => variables
+                .iter()
+                .map(|(key, value)| (scalar(key).unwrap_or_default(), scalar(value)))
+                .collect::<Vec<_>>(),
+            Value::Sequence(variables) => variables
+                .iter()
+                .filter_map(scalar)
+                .map(|variable| match variable.split_once('=') {
+                    Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                    None => (variable, None),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut env = Mapping::new();
+        for (key, value) in variables {
+            match value {
+                Some(value) => {
+                    if value.contains("${") {
+                        self.report(format!("services.{}.environment.{}", name, key), "compose interpolation is kept as written");
+                    }
+                    compose::set(&mut env, &key, value);
+                }
+                None => self.report(format!("services.{}.environment.{}", name, key), "values taken from the host are not supported"),
+            }
+        }
+        if !env.is_empty() {
+            compose::set(component, "env", env);
+        }
+    }
+
+    fn translate_volumes(&mut self, name: &str, volumes: &Value, component: &mut Mapping) {
+        let mut stack_volumes = Mapping::new();
+        for (index, volume) in volumes.as_sequence().cloned().unwrap_or_default().iter().enumerate() {
+            let path = format!("services.{}.volumes[{}]", name, index);
+            let (source, target) = match volume {
+                Value::Mapping(volume) => {
+                    if volume.get("read_only").is_some() {
+                        self.report(path.clone(), "read only mounts are not supported, mounted read-write");
+                    }
+                    (volume.get("source").and_then(scalar), volume.get("target").and_then(scalar))
+                }
+                volume => {
+                    let volume = scalar(volume).unwrap_or_default();
+                    let parts = volume.split(':').map(|p| p.to_string()).collect::<Vec<_>>();
+                    if parts.len() > 2 {
+                        self.report(path.clone(), "mount options are not supported, mounted read-write");
+                    }
+                    match parts.len() {
+                        1 => (None, Some(parts[0].clone())),
+                        _ => (Some(parts[0].clone()), Some(parts[1].clone())),
+                    }
+                }
+            };
+
+            let (source, target) = match (source, target) {
+                (Some(source), Some(target)) => (source, target),
+                _ => {
+                    self.report(path, "anonymous volumes are not supported");
+                    continue;
+                }
+            };
+            let source = if source.starts_with('~') {
+                self.report(path, "paths in the home directory are not supported");
+                continue;
+            } else if source.starts_with('/') {
+                source
+            } else if source.starts_with('.') {
+                self.stack_path(Path::new(&source))
+            } else {
+                format!("{}{}", VOLUME_PREFIX, source)
+            };
+            compose::set(&mut stack_volumes, &source, target);
+        }
+        if !stack_volumes.is_empty() {
+            compose::set(component, "volumes", stack_volumes);
+        }
+    }
+
+    fn translate_depends_on(&mut self, name: &str, depends_on: &Value, component: &mut Mapping) {
+        let dependencies = match depends_on {
+            Value::Mapping(dependencies) => dependencies
+                .iter()
+                .map(|(dependency, options)| {
+                    let dependency = scalar(dependency).unwrap_or_default();
+                    let condition = options.get("condition").and_then(scalar);
+                    if condition.is_some_and(|condition| condition != "service_started") {
+                        self.report(
+                            format!("services.{}.depends_on.{}.condition", name, dependency),
+                            "components only wait for their dependencies to start",
+                        );
+                    }
+                    dependency
+                })
+                .collect::<Vec<_>>(),
+            depends_on => depends_on.as_sequence().map(|d| d.iter().filter_map(scalar).collect()).unwrap_or_default(),
+        };
+        if !dependencies.is_empty() {
+            compose::set(component, "depends_on", dependencies);
+        }
+    }
+
+    fn translate_resource(&mut self, name: &str, key: &str, value: &Value, component: &mut Mapping) {
+        let (resource, quantity) = match key {
+            "mem_limit" => ("memory", scalar(value).map(|memory| memory_quantity(&memory))),
+            _ => ("cpu", scalar(value)),
+        };
+        let mut resources = component.get("resources").and_then(Value::as_mapping).cloned().unwrap_or_default();
+        compose::set(&mut resources, resource, quantity);
+        if Resources::from_yaml(&Value::Mapping(resources.clone()), "").is_err() {
+            self.report(format!("services.{}.{}", name, key), "not a valid amount");
+            return;
+        }
+        compose::set(component, "resources", resources);
+    }
+
+    fn translate_service(&mut self, name: &str, service: &Mapping, color: &str) -> Option<Mapping> {
+        let mut component = Mapping::new();
+        match (service.get("build"), service.get("image")) {
+            (Some(build), image) => {
+                self.translate_build(name, build, &mut component);
+                if image.is_some() {
+                    self.report(format!("services.{}.image", name), "rushd names the images it builds");
+                }
+                for key in ["command", "entrypoint"] {
+                    if service.get(key).is_some() {
+                        self.report(format!("services.{}.{}", name, key), "set it in the Dockerfile");
+                    }
+                }
+            }
+            (None, Some(image)) => {
+                compose::set(&mut component, "build_type", "Image");
+                compose::set(&mut component, "image", scalar(image).unwrap_or_default());
+                for key in ["command", "entrypoint"] {
+                    if let Some(value) = service.get(key) {
+                        self.translate_argument(name, key, value, &mut component);
+                    }
+                }
+            }
+            (None, None) => {
+                self.report(format!("services.{}", name), "has neither an image nor a build and was skipped");
+                return None;
+            }
+        }
+        compose::set(&mut component, "color", color);
+
+        for (key, value) in service {
+            let key = key.as_str().unwrap_or_default();
+            match key {
+                "build" | "image" | "command" | "entrypoint" => (),
+                "ports" => self.translate_ports(name, value, &mut component),
+                "environment" => self.translate_environment(name, value, &mut component),
+                "volumes" => self.translate_volumes(name, value, &mut component),
+                "depends_on" => self.translate_depends_on(name, value, &mut component),
+                "cpus" | "mem_limit" => self.translate_resource(name, key, value, &mut component),
+                "stop_signal" => compose::set(&mut component, "stop_signal", scalar(value).unwrap_or_default()),
+                "stop_grace_period" => match scalar(value).as_deref().and_then(parse_duration) {
+                    Some(seconds) => compose::set(&mut component, "stop_timeout", seconds),
+                    None => self.report(format!("services.{}.stop_grace_period", name), "only whole seconds are supported"),
+                },
+                "container_name" | "networks" => {
+                    self.report(format!("services.{}.{}", name, key), "rushd names containers and networks per product")
+                }
+                key => self.report(format!("services.{}.{}", name, key), "not supported"),
+            }
+        }
+        Some(component)
+    }
+}
+
+/// Translates a compose file into a stack.yaml for the product directory.
+/// Services with an image become `Image` components, services with a build
+/// become `RustBinary` components when their context holds a Cargo.toml and
+/// `Script` components otherwise.
+pub fn import(compose_file: &Path, product_directory: &Path) -> Result<ComposeImport, String> {
+    let content = std::fs::read_to_string(compose_file)
+        .map_err(|e| format!("Failed to read {}: {}", compose_file.display(), e))?;
+    let compose: Mapping = serde_yaml::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", compose_file.display(), e))?;
+    let services = compose
+        .get("services")
+        .and_then(|services| services.as_mapping())
+        .ok_or(format!("{} has no services", compose_file.display()))?;
+
+    let compose_directory = normalize(compose_file.parent().unwrap_or(Path::new(".")));
+    let product_directory = normalize(product_directory);
+    let mut translator = Translator {
+        compose_directory: &compose_directory,
+        product_directory: &product_directory,
+        untranslated: Vec::new(),
+    };
+
+    for (key, value) in &compose {
+        let key = key.as_str().unwrap_or_default();
+        match key {
+            "services" | "version" | "name" => (),
+            "volumes" => {
+                for (volume, definition) in value.as_mapping().cloned().unwrap_or_default() {
+                    if definition.as_mapping().is_some_and(|definition| !definition.is_empty()) {
+                        let volume = scalar(&volume).unwrap_or_default();
+                        translator.report(format!("volumes.{}", volume), "volume options are not supported");
+                    }
+                }
+            }
+            "networks" => translator.report("networks".to_string(), "the components share the product network"),
+            key => translator.report(key.to_string(), "not supported"),
+        }
+    }
+
+    let mut stack = Mapping::new();
+    for (index, (name, service)) in services.iter().enumerate() {
+        let name = scalar(name).unwrap_or_default();
+        let empty = Mapping::new();
+        let service = service.as_mapping().unwrap_or(&empty);
+        if let Some(component) = translator.translate_service(&name, service, COLORS[index % COLORS.len()]) {
+            compose::set(&mut stack, &name, component);
+        }
+    }
+
+    let yaml = serde_yaml::to_string(&stack).map_err(|e| e.to_string())?;
+    let file_name = compose_file.file_name().unwrap_or_default().to_string_lossy();
+    Ok(ComposeImport {
+        stack: format!("# Imported from {} by `rushd import compose`\n{}", file_name, yaml),
+        untranslated: translator.untranslated,
+    })
+}
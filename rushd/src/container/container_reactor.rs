@@ -1,21 +1,30 @@
 use std::{
-    collections::{HashMap, HashSet}, sync::mpsc::{self, Receiver}
+    collections::{BTreeMap, BTreeSet, HashMap}, sync::mpsc::{self, Receiver}
 };
-use tokio::sync::broadcast::{Sender as BroadcastSender, Receiver as BroadcastReceiver};
+use tokio::sync::broadcast::Sender as BroadcastSender;
 use tokio::sync::broadcast;
 use colored::Colorize;
 use super::status::Status;
-use super::docker::DockerImage;
-use crate::builder::ComponentBuildSpec;
+use super::docker::{remove_labelled_containers, DockerImage, ENVIRONMENT_LABEL, PRODUCT_LABEL};
+use super::compose;
+use super::dependency_graph::DependencyGraph;
+use super::ports::{is_port_available, PortAllocator};
+use super::migrations::{Migrations, MIGRATIONS_KEY};
+use super::volumes::{self, Mount, Snapshots};
+use crate::builder::{ComponentBuildSpec, References};
 use std::io::Write;
 use crate::utils::Directory;
 use std::sync::Arc;
 use crate::toolchain::ToolchainContext;
-use notify::{Config as NotifyConfig, RecommendedWatcher, RecursiveMode, Watcher};
 use crate::container::service_spec::{ServiceSpec, ServicesSpec};
+use crate::container::LogFilter;
 use crate::builder::BuildType;
-use crate::gitignore::GitIgnore;
-use crate::cluster::K8ClusterManifests;
+use crate::watcher::{ChangeSet, FileWatcher};
+use crate::proxy::{IngressProxy, Route};
+use crate::control::{ComponentStatus, ControlMessage, ControlRequest, ControlResponse, ControlServer, LogLine};
+use tokio::sync::mpsc as tokio_mpsc;
+use std::path::{Path, PathBuf};
+use crate::cluster::{is_manifest, K8ClusterManifests, LocalCluster};
 use std::sync::Mutex;
 use core::cell::RefCell;
 use std::rc::Rc;
@@ -25,21 +34,33 @@ use crate::builder::Config;
 use crate::cluster::InfrastructureRepo;
 use crate::builder::Variables;
 
+pub const DEFAULT_DEBOUNCE_MS: u64 = 300;
+
+/// Host name under which containers reach processes running on the host
+pub const NATIVE_HOST: &str = "host.docker.internal";
+
+/// Seconds to wait beyond a component's stop timeout before killing it
+const STOP_GRACE_PERIOD: u64 = 5;
+
 // TODO: This ought to split into a spec and a reactor
 pub struct ContainerReactor {
     config: Arc<Config>,
     product_directory: String,
     images: Vec<DockerImage>,
-    handles: HashMap<usize, tokio::task::JoinHandle<()>>,
-    images_by_id: HashMap<usize, DockerImage>,
-    statuses_receivers: HashMap<usize, Receiver<Status>>,
+    dependency_graph: DependencyGraph,
+    migrations: Option<Migrations>,
+    handles: HashMap<String, tokio::task::JoinHandle<()>>,
+    statuses_receivers: HashMap<String, Receiver<Status>>,
     statuses: HashMap<String, Status>,
-    terminate_sender: BroadcastSender<()>,
-    terminate_receiver: BroadcastReceiver<()>,
+    terminate_senders: HashMap<String, BroadcastSender<()>>,
     toolchain: Option<Arc<ToolchainContext>>,
     services: Arc<ServicesSpec>,
     cluster_manifests: K8ClusterManifests,
-    infrastructure_repo: InfrastructureRepo
+    infrastructure_repo: InfrastructureRepo,
+    debounce: std::time::Duration,
+    /// Set by `launch`, which runs the migrations itself. Everywhere else
+    /// they are rendered and applied as a Job.
+    docker_migrations: bool,
 }
 
 impl ContainerReactor {
@@ -87,6 +108,12 @@ impl ContainerReactor {
         &self.cluster_manifests
     }
 
+    pub fn set_log_filter(&mut self, log_filter: LogFilter) {
+        for image in &mut self.images {
+            image.set_log_filter(log_filter.clone());
+        }
+    }
+
     pub fn get_image(&self, component_name: &str) -> Option<&DockerImage> {
         self.images.iter().find(|image| image.component_name() == component_name)
     }
@@ -126,8 +153,8 @@ impl ContainerReactor {
             Err(e) => return Err(format!("Failed to read stack config: {}", e)),
         };
 
-        let mut next_port = 8000;
-        let stack_config_value: serde_yaml::Value = serde_yaml::from_str(&stack_config).unwrap();
+        let stack_config_value: serde_yaml::Value = serde_yaml::from_str(&stack_config)
+            .map_err(|e| format!("Failed to parse stack config: {}", e))?;
         let mut images = Vec::new();
 
         let mut cluster_manifests = {
@@ -136,9 +163,15 @@ impl ContainerReactor {
         };
 
         let mut all_component_specs = Vec::new();
+        let mut migrations = None;
 
         if let serde_yaml::Value::Mapping(config_map) = stack_config_value {
             for (component_name, yaml_section) in config_map {
+                if component_name.as_str() == Some(MIGRATIONS_KEY) {
+                    migrations = Some(Migrations::from_yaml(&yaml_section)?);
+                    continue;
+                }
+
                 let mut yaml_section_clone = yaml_section.clone();
                 
                 if let serde_yaml::Value::Mapping(ref mut yaml_section_map) = yaml_section_clone {
@@ -176,13 +209,6 @@ impl ContainerReactor {
                     BuildType::PureDockerImage{ .. } => (),
                     _ => {
                         image.set_tag(tag.clone());
-                
-                        // We only set the port if it is not specified in the spec
-                        if image.spec().port.is_none() {                            
-                            image.set_port(next_port);
-                            next_port += 1;
-
-                        }
                     }
                 }
                 image.set_toolchain(toolchain.clone());      
@@ -194,15 +220,92 @@ impl ContainerReactor {
             }
         }
 
+        // A native RustBinary binds the port it would listen on inside its
+        // container directly on the host. Trunk is told which port to use, so
+        // native TrunkWasm components get an allocated port instead.
+        for image in &mut images {
+            let spec = image.spec();
+            if spec.dev_mode.is_native() && spec.port.is_none() {
+                if let BuildType::RustBinary{ .. } = spec.build_type {
+                    if let Some(port) = image.target_port() {
+                        image.set_port(port);
+                    }
+                }
+            }
+        }
+
+        // We only allocate a port if it is not specified in the spec
+        let has_fixed_port = |image: &DockerImage| {
+            let spec = image.spec();
+            spec.port.is_some() || (spec.dev_mode.is_native() && matches!(spec.build_type, BuildType::RustBinary{ .. }))
+        };
+
+        let mut reserved_ports: HashMap<u16, String> = HashMap::new();
+        for image in images.iter().filter(|image| has_fixed_port(image)) {
+            if let Some(port) = image.port() {
+                if let Some(other) = reserved_ports.insert(port, image.component_name()) {
+                    return Err(format!("Components `{}` and `{}` both use port {}", other, image.component_name(), port));
+                }
+            }
+        }
+        for image in images.iter() {
+            if let Some(port) = image.spec().https_port {
+                if let Some(other) = reserved_ports.insert(port, image.component_name()) {
+                    return Err(format!("Components `{}` and `{}` both use port {}", other, image.component_name(), port));
+                }
+            }
+        }
+
+        let needs_port = |image: &DockerImage| {
+            !has_fixed_port(image) && !matches!(image.spec().build_type, BuildType::PureDockerImage{ .. })
+        };
+        let mut port_allocator = PortAllocator::load(Path::new("target").join("rushd").join("ports.json"), reserved_ports.keys().cloned().collect());
+        let mut unassigned = Vec::new();
+        for image in images.iter_mut().filter(|image| needs_port(image)) {
+            match port_allocator.persisted_port(&image.component_name()) {
+                Some(port) => image.set_port(port),
+                None => unassigned.push(image),
+            }
+        }
+        for image in unassigned {
+            let port = port_allocator.allocate(&image.component_name())?;
+            image.set_port(port);
+        }
+        port_allocator.save()?;
+
+        // Components with Kubernetes manifests are reached in the cluster on
+        // the port of their container
+        let cluster_ports = images.iter()
+            .filter(|image| image.spec().k8s.is_some())
+            .filter_map(|image| Some((image.component_name(), image.target_port()?)))
+            .collect::<HashMap<_, _>>();
+
+        // Native processes are reached directly on their host port
+        for image in &mut images {
+            if image.spec().dev_mode.is_native() {
+                if let Some(port) = image.port() {
+                    image.set_target_port(port);
+                }
+            }
+        }
+
         let mut services = HashMap::new();
         for image in &images {
             if let Some(port) = image.port() {
                 if let Some(target_port) = image.target_port() {            
+                    let host = if image.spec().dev_mode.is_native() {
+                        NATIVE_HOST.to_string()
+                    } else {
+                        image.component_name()
+                    };
                     let svc_spec = ServiceSpec { 
                         name: image.component_name(), 
+                        host,
                         port, 
                         target_port,
                         mount_point: image.spec().mount_point.clone(),
+                        subdomain: image.spec().subdomain.clone(),
+                        cluster_port: cluster_ports.get(&image.component_name()).cloned(),
                     };
                     services.insert(image.component_name(), svc_spec);
                 }
@@ -214,7 +317,42 @@ impl ContainerReactor {
             component_spec.lock().unwrap().set_services(services.clone());
         }
 
-        let (terminate_sender, terminate_receiver) = broadcast::channel(16);
+        let specs = all_component_specs.iter().map(|spec| spec.lock().unwrap().clone()).collect::<Vec<_>>();
+        let mut references = References::new(&specs, &services);
+        for component_spec in &all_component_specs {
+            let mut spec = component_spec.lock().unwrap();
+            let component_name = spec.component_name.clone();
+            let in_component = |e: String| format!("In `{}`: {}", component_name, e);
+            if spec.env.is_some() {
+                spec.env = Some(references.resolve_component_env(&component_name).map_err(in_component)?);
+            }
+            spec.docker_extra_run_args = spec.docker_extra_run_args
+                .iter()
+                .map(|arg| references.resolve(arg))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(in_component)?;
+            for name in &spec.secret_env {
+                if !spec.env.as_ref().is_some_and(|env| env.contains_key(name)) {
+                    return Err(in_component(format!("secret_env lists {}, which is not in env", name)));
+                }
+            }
+        }
+
+        let dependencies = images.iter()
+            .map(|image| (image.component_name(), image.depends_on().clone()))
+            .collect::<BTreeMap<_, _>>();
+        let dependency_graph = DependencyGraph::new(&dependencies)?;
+
+        if let Some(migrations) = &migrations {
+            if !dependencies.contains_key(migrations.database()) {
+                return Err(format!("Migrations target unknown component `{}`", migrations.database()));
+            }
+            if let Some(component) = migrations.connection_component() {
+                if !dependencies.contains_key(component) {
+                    return Err(format!("Migrations take the connection URL from unknown component `{}`", component));
+                }
+            }
+        }
 
         let infrastructure_repo = InfrastructureRepo::new(config.clone(), toolchain.clone());
 
@@ -224,16 +362,18 @@ impl ContainerReactor {
                 // product_name: product_name.to_string(),
                 product_directory: product_path.to_string(),
                 images: images,
-                images_by_id: HashMap::new(),
+                dependency_graph,
+                migrations,
                 statuses_receivers: HashMap::new(),
                 statuses: HashMap::new(),
                 handles: HashMap::new(),
-                terminate_sender,
-                terminate_receiver,
+                terminate_senders: HashMap::new(),
                 toolchain: Some(toolchain),
                 services,
                 cluster_manifests,
-                infrastructure_repo
+                infrastructure_repo,
+                debounce: std::time::Duration::from_millis(DEFAULT_DEBOUNCE_MS),
+                docker_migrations: false,
             }            
         )
 //        Ok(Self::new(&product_name, &product_path, images, toolchain))
@@ -268,7 +408,7 @@ impl ContainerReactor {
 
         let kubectl = toolchain.kubectl();
 
-        match run_command("Selecting Kubernetes context".white().bold(), &kubectl, vec!["config", "set-context", context]).await {
+        match run_command("Selecting Kubernetes context".white().bold(), &kubectl, vec!["config", "use-context", context]).await {
             Ok(_) => {
                 println!("Kubernetes context set to: {}", context);
                 Ok(())
@@ -303,6 +443,12 @@ impl ContainerReactor {
         }
         */
 
+        self.apply_secrets(kubectl).await?;
+
+        if let Some(migrations) = &self.migrations {
+            migrations.apply(Path::new("."), self.cluster_manifests.output_directory(), &self.config, kubectl).await?;
+        }
+
         match run_command("apply".white().bold(), &kubectl, vec!["apply", "-R", "-f", &output_dir]).await {
             Ok(_) => (),
             Err(e) => {
@@ -341,6 +487,68 @@ impl ContainerReactor {
         Ok(())        
     }
 
+    /// The Secrets the manifests refer to, with their values: one for every
+    /// component with Kubernetes manifests and a `secret_env`, and the
+    /// connection URL of the migrations when it is known.
+    fn secret_values(&self) -> Vec<(String, BTreeMap<String, String>)> {
+        let migrations = self.migrations
+            .as_ref()
+            .and_then(|migrations| migrations.secret_values(&self.images))
+            .map(|values| (Migrations::secret_name(&self.config), values));
+
+        self.images
+            .iter()
+            .map(|image| image.spec())
+            .filter(|spec| spec.k8s.is_some() && !spec.secret_env.is_empty())
+            .map(|spec| {
+                let env = spec.env.clone().unwrap_or_default();
+                let values = spec.secret_env
+                    .iter()
+                    .filter_map(|name| env.get(name).map(|value| (name.clone(), value.clone())))
+                    .collect();
+                (spec.component_name.clone(), values)
+            })
+            .chain(migrations)
+            .collect()
+    }
+
+    /// Creates or updates the Secrets of the components and the migrations.
+    /// The values never go into the rendered manifests, which `rollout`
+    /// commits, so they are written under `target/rushd` only for as long
+    /// as kubectl needs them.
+    async fn apply_secrets(&self, kubectl: &str) -> Result<(), String> {
+        let namespace = format!("{}-{}", self.config.product_uri(), self.config.environment());
+        let directory = Path::new("target").join("rushd").join("secrets");
+        for (name, values) in self.secret_values() {
+            let manifests = [
+                serde_json::json!({
+                    "apiVersion": "v1",
+                    "kind": "Namespace",
+                    "metadata": { "name": namespace },
+                }),
+                serde_json::json!({
+                    "apiVersion": "v1",
+                    "kind": "Secret",
+                    "metadata": { "name": name, "namespace": namespace },
+                    "type": "Opaque",
+                    "stringData": values,
+                }),
+            ];
+            let documents = manifests
+                .iter()
+                .map(|manifest| serde_yaml::to_string(manifest).map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            std::fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+            let file = directory.join(format!("{}.yaml", name));
+            std::fs::write(&file, documents.join("---\n")).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+            let result = run_command("secrets".white().bold(), kubectl, vec!["apply", "-f", &file.display().to_string()]).await;
+            let _ = std::fs::remove_file(&file);
+            result?;
+        }
+        Ok(())
+    }
+
     pub async fn unapply(&mut self) -> Result<(), String> {
         let toolchain = match self.toolchain.clone() {
             Some(toolchain) => toolchain,
@@ -356,17 +564,10 @@ impl ContainerReactor {
             &output_dir
         };
 
-        let mut args = glob(&format!("{}/**/*.yaml", output_dir)).expect("Failed to read glob pattern")            
-            .filter_map(|e| match e {
-                Ok(e) => {
-                    if e.extension().and_then(std::ffi::OsStr::to_str) == Some("yaml") {
-                        Some(e.display().to_string())
-                    } else {
-                        None
-                    }
-                }
-                Err(_) => None
-            })    
+        let mut args = glob(&format!("{}/**/*", output_dir)).expect("Failed to read glob pattern")
+            .filter_map(|e| e.ok())
+            .filter(|e| is_manifest(e))
+            .map(|e| e.display().to_string())
             .collect::<Vec<_>>();
         args.sort();   
         args.reverse();
@@ -399,6 +600,17 @@ impl ContainerReactor {
 
         self.infrastructure_repo.commit_and_push(&format!("Deploying {} for {}", self.config.environment(), self.config.product_name())).await?;
 
+        for (name, values) in self.secret_values() {
+            println!(
+                "{} the Secret `{}` with {} is not committed and must exist in {}-{}",
+                "Rollout:".yellow().bold(),
+                name,
+                values.keys().cloned().collect::<Vec<_>>().join(", "),
+                self.config.product_uri(),
+                self.config.environment()
+            );
+        }
+
         Ok(())
     }
 
@@ -414,6 +626,130 @@ impl ContainerReactor {
     }
 
 
+    /// Runs the product on a local cluster: builds the images for the host,
+    /// loads them into the cluster and applies the manifests. Components
+    /// whose files change are rebuilt, loaded and restarted until Ctrl-C.
+    pub async fn cluster_dev(&mut self, cluster: &dyn LocalCluster) -> Result<(), String> {
+        let mut watcher = FileWatcher::new(Path::new(&self.product_directory), self.watch_exclusions(), self.debounce)?;
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+
+        // Everything is deployed at first and after a failed deploy
+        let mut components = None;
+        loop {
+            let deployed = match self.deploy_to_cluster(cluster, components.as_ref()).await {
+                Ok(_) => {
+                    println!("{}", format!("Deployed to {} at {}", cluster.name(), self.config.domain()).white().bold());
+                    true
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    println!("{}", "Deploy was unsuccessful, waiting for changes".red().bold());
+                    false
+                }
+            };
+
+            tokio::select! {
+                _ = &mut ctrl_c => {
+                    println!("Termination signal received. The product keeps running on {}.", cluster.name());
+                    return Ok(());
+                }
+                change_set = watcher.next_change_set() => {
+                    let change_set = change_set.ok_or("File watcher stopped unexpectedly")?;
+                    Self::print_change_set(&change_set, "Redeploying changed components.");
+                    components = if deployed { Some(self.changed_components(&change_set)?) } else { None };
+                }
+            }
+        }
+    }
+
+    async fn deploy_to_cluster(&mut self, cluster: &dyn LocalCluster, components: Option<&BTreeSet<String>>) -> Result<(), String> {
+        let loaded = self.load_into_cluster(cluster, components).await?;
+        self.build_manifests().await?;
+        self.apply().await?;
+
+        // A rebuilt image keeps its tag, so the pods have to be replaced
+        if components.is_some() {
+            let kubectl = self.toolchain.as_ref().ok_or("Toolchain not found")?.kubectl().to_string();
+            let namespace = format!("{}-{}", self.config.product_uri(), self.config.environment());
+            for component in loaded {
+                let deployment = format!("deployment/{}", component);
+                run_command("restart".white().bold(), &kubectl, vec!["rollout", "restart", &deployment, "-n", &namespace]).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the images of the components deployed to Kubernetes, only
+    /// `components` if given, and loads them into the cluster under the name
+    /// the manifests refer to. Returns the components that were loaded.
+    async fn load_into_cluster(&mut self, cluster: &dyn LocalCluster, components: Option<&BTreeSet<String>>) -> Result<Vec<String>, String> {
+        let toolchain = self.toolchain.clone().ok_or("Toolchain not found")?;
+        let _guard = Directory::chdir(&self.product_directory);
+
+        let mut loaded = Vec::new();
+        for image in &self.images {
+            let spec = image.spec();
+            if spec.k8s.is_none() || spec.is_builtin_ingress() {
+                continue;
+            }
+            if components.is_some_and(|components| !components.contains(&spec.component_name)) {
+                continue;
+            }
+            if !matches!(
+                spec.build_type,
+                BuildType::TrunkWasm { .. } | BuildType::RustBinary { .. } | BuildType::Script { .. } | BuildType::Ingress { .. }
+            ) {
+                continue;
+            }
+
+            print!("Building {}  ..... ", image.identifier());
+            std::io::stdout().flush().expect("Failed to flush stdout");
+            if let Err(e) = image.build().await {
+                println!("Building {}  ..... [ {} ]", image.identifier(), "FAIL".red().bold());
+                println!();
+                println!("{}", e);
+                return Err("Build was unsuccessful".to_string());
+            }
+            println!("Building {}  ..... [  {}  ]", image.identifier(), "OK".white().bold());
+
+            let tag = image.tagged_image_name();
+            let cluster_tag = format!("{}/{}", self.config.docker_registry(), tag);
+            run_command("tag".white().bold(), toolchain.docker(), vec!["tag", &tag, &cluster_tag]).await?;
+            cluster.load_image(&cluster_tag).await?;
+            loaded.push(spec.component_name.clone());
+        }
+        Ok(loaded)
+    }
+
+    /// Components whose sources, Dockerfile or watched path hold one of the
+    /// changed files.
+    fn changed_components(&self, change_set: &ChangeSet) -> Result<BTreeSet<String>, String> {
+        let product_directory = self.absolute_product_directory()?;
+        let mut changed = BTreeSet::new();
+        for image in &self.images {
+            let spec = image.spec();
+            let mut sources = Vec::new();
+            match &spec.build_type {
+                BuildType::TrunkWasm { location, dockerfile_path, .. }
+                | BuildType::RustBinary { location, dockerfile_path, .. }
+                | BuildType::Script { location, dockerfile_path, .. } => {
+                    sources.push(location.clone());
+                    sources.push(dockerfile_path.clone());
+                }
+                BuildType::Ingress { dockerfile_path, .. } => sources.push(dockerfile_path.clone()),
+                _ => (),
+            }
+            sources.extend(spec.watch_path.clone());
+
+            let sources = sources.iter().map(|source| product_directory.join(source)).collect::<Vec<_>>();
+            if change_set.paths().iter().any(|path| sources.iter().any(|source| path.starts_with(source))) {
+                changed.insert(spec.component_name.clone());
+            }
+        }
+        Ok(changed)
+    }
+
     pub async fn install_manifests(&mut self) -> Result<(), String> {
         let toolchain = match self.toolchain.clone() {
             Some(toolchain) => toolchain,
@@ -531,111 +867,120 @@ impl ContainerReactor {
             println!("\rCreating K8s {}  ..... [  {}  ]", render_dir.display(), "OK".white().bold());
         }
 
+        if let Some(migrations) = &self.migrations {
+            if !self.docker_migrations {
+                migrations.render_manifests(Path::new("."), output_dir, &self.config, &self.images)?;
+            }
+        }
+
         Ok(())
     }
 
     pub async fn build(&mut self) -> Result<(), String> {
-        {
-            let _guard = Directory::chdir(&self.product_directory);
-
-            for image in &mut self.images {
-                print!("Building {}  ..... ", image.identifier());
-                std::io::stdout().flush().expect("Failed to flush stdout");
-                match image.build().await {
-                    Ok(_) => println!("Building {}  ..... [  {}  ]", image.identifier(), "OK".white().bold()),
-                    Err(e) => { 
-                        println!("Building {}  ..... [ {} ]",image.identifier(), "FAIL".red().bold());
-                        println!("");
-                        println!("{}", e);
-                        println!("");
-                        println!("{}", "Build was unsuccessful".red().bold());
-                        return Err(e);
-                    }
+        self.build_images(false).await?;
+        self.build_manifests().await?;
+
+        Ok(())
+    }
+
+    /// Builds for `rushd dev`: components with `dev_mode: native` are
+    /// compiled on the host instead of being packaged into an image.
+    pub async fn build_dev(&mut self) -> Result<(), String> {
+        self.build_images(true).await?;
+        self.build_manifests().await?;
+
+        Ok(())
+    }
+
+    async fn build_images(&mut self, dev: bool) -> Result<(), String> {
+        let _guard = Directory::chdir(&self.product_directory);
+
+        for image in &mut self.images {
+            print!("Building {}  ..... ", image.identifier());
+            std::io::stdout().flush().expect("Failed to flush stdout");
+            let result = if dev {
+                match image.build_native().await {
+                    Ok(true) => Ok(()),
+                    Ok(false) => image.build().await,
+                    Err(e) => Err(e),
+                }
+            } else {
+                image.build().await
+            };
+            match result {
+                Ok(_) => println!("Building {}  ..... [  {}  ]", image.identifier(), "OK".white().bold()),
+                Err(e) => { 
+                    println!("Building {}  ..... [ {} ]",image.identifier(), "FAIL".red().bold());
+                    println!("");
+                    println!("{}", e);
+                    println!("");
+                    println!("{}", "Build was unsuccessful".red().bold());
+                    return Err(e);
                 }
             }
         }
 
-        self.build_manifests().await?;
-
         Ok(())
     }
 
+    pub fn set_debounce(&mut self, debounce: std::time::Duration) {
+        self.debounce = debounce;
+    }
+
+    /// Directories and files written by rushd itself. Changes to these must
+    /// never trigger a rebuild.
+    fn watch_exclusions(&self) -> Vec<PathBuf> {
+        let product_directory = Path::new(&self.product_directory);
+        let mut excluded = vec![
+            product_directory.join("target").join("rushd"),
+            product_directory.join(self.cluster_manifests.output_directory()),
+        ];
+
+        for image in &self.images {
+            let spec = image.spec();
+
+            // `trunk serve` rebuilds and reloads the frontend on its own
+            if let BuildType::TrunkWasm{ location, .. } = &spec.build_type {
+                if spec.dev_mode.is_native() {
+                    excluded.push(product_directory.join(location));
+                }
+            }
+
+            let artefact_output_dir = product_directory.join(&spec.artefact_output_dir);
+            if let Some(artefacts) = &spec.artefacts {
+                for output_path in artefacts.values() {
+                    excluded.push(artefact_output_dir.join(output_path));
+                }
+            }
+            excluded.push(artefact_output_dir);
+        }
+
+        excluded
+    }
+
     pub async fn launch(&mut self) ->  Result<(), String>  {
+        self.docker_migrations = true;
         self.clean().await;
                 
         let _ = self.create_network().await;
 
-        let mut running  = true;
-        let (watch_tx, watch_rx) = std::sync::mpsc::channel();
-        // Automatically select the best implementation for your platform.
-        // You can also access each implementation directly e.g. INotifyWatcher.
-        let mut watcher = match RecommendedWatcher::new(watch_tx, NotifyConfig::default()) {
-            Ok(w) => w,
-            Err(e) => return Err(e.to_string()),
-        };
+        self.check_ports_available()?;
 
-        // Add a path to be watched. All files and directories at that path and
-        // below will be monitored for changes.
-        let path = self.product_directory.clone();
-        match watcher.watch(path.as_ref(), RecursiveMode::Recursive) {
-            Ok(_) => (),
-            Err(e) => return Err(e.to_string()),
-        }
-
-        let product_directory = std::path::Path::new(&self.product_directory);
-        let gitignore = GitIgnore::new(&product_directory);        
-        let test_if_files_changed =  move || { 
-            if let Ok(event) = watch_rx.try_recv() {
-                match event {
-                    Ok(event) => {
-                        // tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
-                        let other_events = watch_rx.try_iter();
-
-                        let all_events = std::iter::once(Ok(event)).chain(other_events);
-                                       // .filter(|e| e.is_ok()).map(|e| e.unwrap());
-                        let paths = all_events
-                            .filter_map(|event| {
-                                if let Ok(event) = event {
-                                    if event.paths.is_empty() {
-                                        None
-                                    } else {
-                                        Some(event.paths)
-                                    }
-                                } else {
-                                    None
-                                }                            
-                            })
-                            .into_iter().flatten()
-                            .filter(|path| !gitignore.ignores(path))
-                            .collect::<Vec<_>>();
-
-                        let paths = paths.into_iter()
-                            .filter(|path| path.is_file())
-                            .collect::<Vec<_>>();
-
-                        let mut unique_paths = std::collections::HashSet::new();
-                        let paths = paths.into_iter()
-                            .filter(|path| unique_paths.insert(path.clone()))
-                            .collect::<Vec<_>>();
-
-                        if !paths.is_empty() {
-                            println!("{:#?}", paths);
-                            return true;
-                        } 
-                    },
-                    Err(e) => {
-                        eprintln!("Watch error: {:?}", e);
+        let mut running  = true;
+        let mut watcher = FileWatcher::new(Path::new(&self.product_directory), self.watch_exclusions(), self.debounce)?;
+        let ingress_proxy = self.start_ingress_proxy().await?;
 
-                    }
-                }
-            }
-            false        
-        };
+        let (log_sender, _) = broadcast::channel::<LogLine>(1024);
+        for image in &mut self.images {
+            image.set_log_sender(log_sender.clone());
+        }
+        let (control_sender, mut control_receiver) = tokio_mpsc::unbounded_channel::<ControlMessage>();
+        let _control_server = ControlServer::start(self.control_socket_path(), control_sender, log_sender).await?;
 
         // TODO: Update watch for the individual components    
 
         while running {
-            match self.build().await {
+            match self.build_dev().await {
                 Ok(_) => (),
                 Err(e) => {
                     let e = e.replace("error:", &format!("{}:", &"error".red().bold().to_string()))
@@ -650,161 +995,567 @@ impl ContainerReactor {
 
                     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
-
-                    // // Clearing events arising from build
-                    // while  watch_rx.try_recv().is_ok() {
-                    //     tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-                    // }
-
-
                     loop {
-                        if test_if_files_changed() {
-                            println!("File change detected. Rebuilding all images.");
-                            let _ = self.terminate_sender.send(());                                        
-                            break;
-                        }
-        
                         tokio::select! {
                             _ = &mut ctrl_c => {
-                                println!("Termination signal received. Sending SIGTERM to all subprocesses.");
-                                let _ = self.terminate_sender.send(());
+                                println!("Termination signal received.");
                                 running = false;
-                                break;
                             }
-                            _ = tokio::time::sleep(tokio::time::Duration::from_millis(10)) => {
-                                // Status update loop
+                            change_set = watcher.next_change_set() => {
+                                match change_set {
+                                    Some(change_set) => {
+                                        Self::print_change_set(&change_set, "Rebuilding all images.");
+                                        self.reload_ingress_routes(ingress_proxy.as_ref(), &change_set);
+                                    }
+                                    None => return Err("File watcher stopped unexpectedly".to_string()),
+                                }
+                            }
+                            Some((request, reply)) = control_receiver.recv() => {
+                                // A rebuild retries the failed build, nothing else can run
+                                if let ControlRequest::Rebuild { .. } = request {
+                                    let _ = reply.send(ControlResponse::ok("Rebuilding all components"));
+                                } else {
+                                    let _ = reply.send(ControlResponse::error("The last build failed, the stack is not running"));
+                                    continue;
+                                }
                             }
                         }
-                    }           
-
+                        break;
+                    }
 
                     continue;
                 }
             }
 
-            // Clearing events
-            // while watch_rx.try_recv().is_ok() {  }
-            let max_label_length = self.images.iter().map(|image| image.component_name().len()).max().unwrap_or_default();
-            self.images_by_id = HashMap::new();
-            self.statuses_receivers = HashMap::new();
-            self.statuses = HashMap::new();
-            self.handles = HashMap::new();
+            self.start_all().await;
 
-            let dependency_graph = self.images.iter().map(|image| (image.image_name().to_string(), image.depends_on().clone())).collect::<HashMap<String, Vec<String>>>();
-            
-            // Computing the deploy priority
-            // TODO: Suboptimal algorithm - can be improved
-            let mut longest_paths = HashMap::new();
-            for (name, _) in &dependency_graph {
-                let mut stack = vec![(name, 1)]; // (current node, current path length)
-                let mut visited = HashSet::new();
-                let mut max_length = 1;
-
-                while let Some((current, path_len)) = stack.pop() {
-                    visited.insert(current);
-                    max_length = max_length.max(path_len);
-
-                    if let Some(deps) = dependency_graph.get(current) {
-                        for dep in deps {
-                            if !visited.contains(dep) {
-                                stack.push((dep, path_len + 1));
+            let ctrl_c = tokio::signal::ctrl_c();
+            tokio::pin!(ctrl_c);
+
+            loop {
+                tokio::select! {
+                    _ = &mut ctrl_c => {
+                        println!("Termination signal received. Sending SIGTERM to all subprocesses.");
+                        running = false;
+                        break;
+                    }
+                    change_set = watcher.next_change_set() => {
+                        match change_set {
+                            Some(change_set) => {
+                                Self::print_change_set(&change_set, "Rebuilding all images.");
+                                self.reload_ingress_routes(ingress_proxy.as_ref(), &change_set);
+                            }
+                            None => {
+                                eprintln!("File watcher stopped unexpectedly.");
+                                running = false;
                             }
                         }
+                        break;
+                    }
+                    Some((request, reply)) = control_receiver.recv() => {
+                        let response = self.handle_control(request).await;
+                        let _ = reply.send(response);
+                    }
+                    _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
+                        self.update_image_statuses();
                     }
                 }
+            }
+            self.stop_all().await;
+            self.clean().await;
+        }
+
+        let _ = self.delete_network().await;
+
+        Ok(())
+    }
+
+    fn ingress_routes(&self) -> Option<Vec<Route>> {
+        let image = self.images.iter().find(|image| image.spec().is_builtin_ingress())?;
+        match image.spec().build_type {
+            BuildType::Ingress{ components, .. } => Some(Route::from_services(&self.services, &components)),
+            _ => None,
+        }
+    }
 
-                longest_paths.insert(name.clone(), max_length);
+    /// Starts the proxy of an Ingress component with `ingress_mode: builtin`.
+    /// It is started once and keeps serving while components restart.
+    async fn start_ingress_proxy(&self) -> Result<Option<IngressProxy>, String> {
+        let image = match self.images.iter().find(|image| image.spec().is_builtin_ingress()) {
+            Some(image) => image,
+            None => return Ok(None),
+        };
+        let routes = self.ingress_routes().unwrap_or_default();
+        let port = image.port().ok_or(format!("No port assigned to {}", image.component_name()))?;
+
+        let max_label_length = self.images.iter().map(|image| image.component_name().len()).max().unwrap_or_default();
+        let label = format!("{:width$}", image.component_name(), width = max_label_length).color(image.spec().color.as_str()).bold();
+        IngressProxy::start(port, routes, label).await.map(Some)
+    }
+
+    /// Re-reads the stack when it changes and hands the new routes to the
+    /// running proxy, so routing changes apply without restarting it. A
+    /// stack the routes cannot be read from keeps the old routes.
+    fn reload_ingress_routes(&self, ingress_proxy: Option<&IngressProxy>, change_set: &ChangeSet) {
+        let ingress_proxy = match ingress_proxy {
+            Some(ingress_proxy) => ingress_proxy,
+            None => return,
+        };
+        if !change_set.paths().iter().any(|path| path.file_name().is_some_and(|name| name == "stack.yaml")) {
+            return;
+        }
+
+        match self.edited_ingress_routes() {
+            Ok(routes) => {
+                ingress_proxy.set_routes(routes);
+                println!("{}", "Ingress routes reloaded".white().bold());
             }
+            Err(e) => eprintln!("Failed to reload ingress routes: {}", e),
+        }
+    }
 
+    /// Routes for the edited stack, without loading it again. Only the
+    /// ingress components and their mount points and subdomains are read
+    /// from the edit. Ports stay those of the running services, so routing
+    /// to a component that has no port yet needs a restart.
+    fn edited_ingress_routes(&self) -> Result<Vec<Route>, String> {
+        let ingress = self.images.iter().find(|image| image.spec().is_builtin_ingress()).ok_or("No built-in ingress")?;
+        let variables = ingress.spec().variables;
+
+        let path = Path::new(&self.product_directory).join("stack.yaml");
+        let stack = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let stack: serde_yaml::Value = serde_yaml::from_str(&stack).map_err(|e| format!("Failed to parse stack config: {}", e))?;
+        let section = |name: &str| stack.get(name).ok_or(format!("`{}` is not in the stack", name));
+        let string = |section: &serde_yaml::Value, key: &str| -> Result<Option<String>, String> {
+            match section.get(key) {
+                None => Ok(None),
+                Some(value) => {
+                    let value = value.as_str().ok_or(format!("{} must be a string", key))?;
+                    ComponentBuildSpec::try_process_template_string(value, &variables).map(Some)
+                }
+            }
+        };
 
-            let mut jobs = self.images.iter_mut().enumerate().map(move |(id, image)| {
-                let priority = longest_paths.get(image.image_name()).cloned().unwrap_or_default();
+        let components = section(&ingress.component_name())?
+            .get("components")
+            .and_then(|components| components.as_sequence())
+            .ok_or("components are required for Ingress")?
+            .iter()
+            .map(|component| component.as_str().map(|c| c.to_string()).ok_or("components must be names".to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut services = ServicesSpec::new();
+        for component in &components {
+            let mut service = self.services
+                .get(component)
+                .cloned()
+                .ok_or(format!("`{}` has no port yet, restart rushd dev to route to it", component))?;
+            let section = section(component)?;
+            service.mount_point = string(section, "mount_point")?;
+            service.subdomain = string(section, "subdomain")?;
+            services.insert(component.clone(), service);
+        }
+        Ok(Route::from_services(&services, &components))
+    }
 
-                (priority, id, image)
-            }).collect::<Vec<_>>();
-            jobs.sort_by(|a, b| a.0.cmp(&b.0)); // Sort jobs by priority in descending order
+    /// Probes the host ports of all services before anything binds them, so
+    /// a port taken by another program is reported up front.
+    fn check_ports_available(&self) -> Result<(), String> {
+        let mut ports = self.services.values()
+            .map(|service| (service.port, service.name.clone()))
+            .collect::<Vec<_>>();
+        for image in &self.images {
+            if let Some(port) = image.spec().https_port {
+                ports.push((port, image.component_name()));
+            }
+            if image.spec().is_builtin_ingress() {
+                if let Some(port) = image.port() {
+                    ports.push((port, image.component_name()));
+                }
+            }
+        }
+        ports.sort();
 
-            for (priory, image_id, image) in jobs {
-                println!("{}", format!("\nStarting {} with priority {}", image.image_name(), priory).white().bold());
-                let (status_sender, status_receiver) = mpsc::channel();
-                self.images_by_id.insert(image_id, image.clone());
-                self.statuses_receivers.insert(image_id, status_receiver);
-                self.statuses.insert(image.component_name(), Status::Awaiting);
-                let handle = image.launch(max_label_length, self.terminate_receiver.resubscribe(), status_sender);
-                self.handles.insert(image_id, handle);
+        let busy = ports.iter()
+            .filter(|(port, _)| !is_port_available(*port))
+            .map(|(port, name)| format!("{} ({})", port, name))
+            .collect::<Vec<_>>();
 
-                // TODO: Hack instead of waiting for the image to declare ready
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        if busy.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Host ports already in use: {}", busy.join(", ")))
+        }
+    }
+
+    /// Width of the widest component name, for aligning log prefixes
+    fn max_label_length(&self) -> usize {
+        self.images.iter().map(|image| image.component_name().len()).max().unwrap_or_default()
+    }
+
+    fn start_component(&mut self, component_name: &str, max_label_length: usize) {
+        let image = match self.images.iter_mut().find(|image| image.component_name() == component_name) {
+            Some(image) => image,
+            None => return,
+        };
+        let (status_sender, status_receiver) = mpsc::channel();
+        let (terminate_sender, terminate_receiver) = broadcast::channel(1);
+        self.statuses_receivers.insert(component_name.to_string(), status_receiver);
+        self.statuses.insert(component_name.to_string(), Status::Awaiting);
+        let handle = image.launch(max_label_length, terminate_receiver, status_sender);
+        self.handles.insert(component_name.to_string(), handle);
+        self.terminate_senders.insert(component_name.to_string(), terminate_sender);
+    }
+
+    /// Launches all components wave by wave. Components within a wave do not
+    /// depend on each other and are started together.
+    async fn start_all(&mut self) {
+        let max_label_length = self.max_label_length();
+        self.statuses_receivers = HashMap::new();
+        self.statuses = HashMap::new();
+        self.handles = HashMap::new();
+        self.terminate_senders = HashMap::new();
+
+        let waves = self.dependency_graph.waves().clone();
+        for (index, wave) in waves.iter().enumerate() {
+            // The built-in ingress proxy runs for the whole session
+            let wave = wave.iter()
+                .filter(|component_name| !self.get_image(component_name).is_some_and(|image| image.spec().is_builtin_ingress()))
+                .cloned()
+                .collect::<Vec<_>>();
+            if wave.is_empty() {
+                continue;
             }
 
-            let ctrl_c = tokio::signal::ctrl_c();
-            tokio::pin!(ctrl_c);
+            println!("{}", format!("\nStarting {}", wave.join(", ")).white().bold());
+            for component_name in &wave {
+                self.start_component(component_name, max_label_length);
+            }
 
-            loop {
-                self.update_image_statuses();
-                if test_if_files_changed() {
-                    println!("File change detected. Rebuilding all images.");
-                    let _ = self.terminate_sender.send(());                                        
-                    break;
+            if let Some(migrations) = &self.migrations {
+                if wave.iter().any(|component_name| component_name == migrations.database()) {
+                    self.run_migrations().await;
                 }
+            }
 
-                tokio::select! {
-                    _ = &mut ctrl_c => {
-                        println!("Termination signal received. Sending SIGTERM to all subprocesses.");
-                        let _ = self.terminate_sender.send(());
-                        running = false;
-                        break;
+            // TODO: Hack instead of waiting for the images to declare ready
+            if index + 1 < waves.len() {
+                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+            }
+        }
+    }
+
+    /// Applies pending migrations once the database has been started. A
+    /// failed migration is reported, the remaining components are started
+    /// regardless so the error can be fixed while the stack runs.
+    async fn run_migrations(&self) {
+        let (migrations, toolchain) = match (&self.migrations, &self.toolchain) {
+            (Some(migrations), Some(toolchain)) => (migrations, toolchain),
+            _ => return,
+        };
+
+        println!("{}", format!("\nMigrating {}", migrations.database()).white().bold());
+        let product_directory = Path::new(&self.product_directory);
+        if let Err(e) = migrations.run_dev(product_directory, toolchain.docker(), self.config.network_name(), &self.images).await {
+            eprintln!("{}", "Migrations failed".red().bold());
+            eprintln!("{}", e);
+        }
+    }
+
+    async fn stop_component(&mut self, component_name: &str) {
+        if let Some(terminate_sender) = self.terminate_senders.remove(component_name) {
+            let _ = terminate_sender.send(());
+        }
+        if let Some(handle) = self.handles.remove(component_name) {
+            let components = vec![component_name.to_string()];
+            self.await_stopped(&components, vec![handle]).await;
+        }
+        if let Some(image) = self.get_image(component_name) {
+            image.clean().await;
+        }
+        self.update_image_statuses();
+        self.statuses.insert(component_name.to_string(), Status::Terminate);
+    }
+
+    async fn rebuild_component(&mut self, component_name: &str) -> Result<(), String> {
+        self.stop_component(component_name).await;
+
+        let _guard = Directory::chdir(&self.product_directory);
+        let image = self.get_image_or_err(component_name)?;
+        if !image.build_native().await? {
+            image.build().await?;
+        }
+        Ok(())
+    }
+
+    pub fn control_socket_path(&self) -> PathBuf {
+        let product_directory = Path::new(&self.product_directory);
+        let product_directory = product_directory.canonicalize().unwrap_or(product_directory.to_path_buf());
+        product_directory.join("target").join("rushd").join("control.sock")
+    }
+
+    fn component_statuses(&mut self) -> Vec<ComponentStatus> {
+        self.update_image_statuses();
+        self.images.iter()
+            .map(|image| ComponentStatus {
+                component: image.component_name(),
+                status: self.statuses.get(&image.component_name()).cloned(),
+                port: image.port(),
+            })
+            .collect()
+    }
+
+    /// Answers a request of `rushd ctl` while the stack is running.
+    async fn handle_control(&mut self, request: ControlRequest) -> ControlResponse {
+        let component_name = match &request {
+            ControlRequest::Status => {
+                return ControlResponse {
+                    ok: true,
+                    message: None,
+                    components: Some(self.component_statuses()),
+                };
+            }
+            ControlRequest::Logs { .. } => return ControlResponse::error("Logs are streamed by the control server"),
+            ControlRequest::Restart { component } | ControlRequest::Rebuild { component } | ControlRequest::Stop { component } => component.clone(),
+        };
+
+        match self.get_image(&component_name) {
+            Some(image) if image.spec().is_builtin_ingress() => {
+                return ControlResponse::error(&format!("{} is served by the built-in proxy", component_name));
+            }
+            Some(_) => (),
+            None => return ControlResponse::error(&format!("Unknown component `{}`", component_name)),
+        }
+
+        let max_label_length = self.max_label_length();
+        match request {
+            ControlRequest::Stop { .. } => {
+                println!("{}", format!("Stopping {}", component_name).white().bold());
+                self.stop_component(&component_name).await;
+                ControlResponse::ok(&format!("Stopped {}", component_name))
+            }
+            ControlRequest::Restart { .. } => {
+                println!("{}", format!("Restarting {}", component_name).white().bold());
+                self.stop_component(&component_name).await;
+                self.start_component(&component_name, max_label_length);
+                ControlResponse::ok(&format!("Restarted {}", component_name))
+            }
+            _ => {
+                println!("{}", format!("Rebuilding {}", component_name).white().bold());
+                match self.rebuild_component(&component_name).await {
+                    Ok(_) => {
+                        self.start_component(&component_name, max_label_length);
+                        ControlResponse::ok(&format!("Rebuilt and restarted {}", component_name))
                     }
-                    _ = tokio::time::sleep(tokio::time::Duration::from_millis(10)) => {
-                        // Status update loop
+                    Err(e) => {
+                        println!("{}", e);
+                        ControlResponse::error(&format!("Building {} failed, it stays stopped:\n{}", component_name, e))
                     }
                 }
             }
-            println!("Joining all handles");
-            // Wait for all images to complete concurrently
-            loop {
-                tokio::select! {
-                    _ = futures::future::join_all(self.handles.values_mut()) => {
-                        break;
-                    },
-                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(5)) => {
-                        println!("Waiting for processes to quit ...");                        
-                        let _ = self.terminate_sender.send(());
-                        self.kill_all_images().await;
-                    },
-                }
+        }
+    }
+
+    /// Waits for terminated components to exit. Each component is killed
+    /// after its stop timeout when it is launched, the grace period on top
+    /// only covers a Docker daemon that does not respond.
+    async fn await_stopped(&self, components: &[String], mut handles: Vec<tokio::task::JoinHandle<()>>) {
+        let stop_timeout = self.images.iter()
+            .filter(|image| components.contains(&image.component_name()))
+            .map(|image| image.spec().stop_timeout)
+            .max()
+            .unwrap_or_default();
+        let timeout = tokio::time::Duration::from_secs(stop_timeout + STOP_GRACE_PERIOD);
+
+        if tokio::time::timeout(timeout, futures::future::join_all(handles.iter_mut())).await.is_err() {
+            println!("{}", format!("{} did not stop, killing", components.join(", ")).red().bold());
+            for image in self.images.iter().filter(|image| components.contains(&image.component_name())) {
+                image.kill().await;
+            }
+            for handle in handles {
+                handle.abort();
             }
-            self.handles.clear();
-            self.clean().await;
         }
+    }
 
-        let _ = self.delete_network().await;
+    /// Stops all components in reverse startup order, so that nothing loses
+    /// a dependency while it is still running.
+    async fn stop_all(&mut self) {
+        let waves = self.dependency_graph.waves().clone();
+        for wave in waves.iter().rev() {
+            for component_name in wave {
+                if let Some(terminate_sender) = self.terminate_senders.remove(component_name) {
+                    let _ = terminate_sender.send(());
+                }
+            }
 
-        Ok(())
+            let handles = wave.iter()
+                .filter_map(|component_name| self.handles.remove(component_name))
+                .collect::<Vec<_>>();
+            self.await_stopped(wave, handles).await;
+        }
+        self.handles.clear();
     }
 
-    async fn  kill_all_images(&mut self) {
-        for image in &mut self.images {
-            image.kill().await;
+    fn print_change_set(change_set: &ChangeSet, action: &str) {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        println!("{}", format!("File change detected in {} file(s). {}", change_set.paths().len(), action).white().bold());
+        for path in change_set.paths() {
+            let path = path.strip_prefix(&current_dir).unwrap_or(path);
+            println!("    {}", path.display());
         }
     }
 
     fn update_image_statuses(&mut self) {
-        for (id, receiver) in self.statuses_receivers.iter_mut() {
+        for (component_name, receiver) in self.statuses_receivers.iter_mut() {
             while let Ok(status) = receiver.try_recv() {
                 match status {
-                    Status::InProgress => println!("Image {} is running", id),
-                    Status::StartupCompleted => println!("Image {} is ready", id),
-                    Status::Finished(code) => println!("Image {} exited with code {}", id, code),
+                    Status::InProgress => println!("Component {} is running", component_name),
+                    Status::StartupCompleted => println!("Component {} is ready", component_name),
+                    Status::Finished(code) => println!("Component {} exited with code {}", component_name, code),
                     _ => (),
                 }
+                self.statuses.insert(component_name.clone(), status);
+            }
+        }
+    }
+
+    fn get_image_or_err(&self, component_name: &str) -> Result<&DockerImage, String> {
+        self.get_image(component_name).ok_or(format!("Unknown component `{}`", component_name))
+    }
+
+    /// Builds and runs a single component with the ports, environment,
+    /// volumes and network it gets in `rushd dev`.
+    pub async fn run_component(&mut self, component_name: &str) -> Result<(), String> {
+        let _guard = Directory::chdir(&self.product_directory);
+
+        let image = self.get_image_or_err(component_name)?;
+        image.clean().await;
+        let _ = self.create_network().await;
+
+        if let Some(port) = image.port() {
+            if !is_port_available(port) {
+                return Err(format!("Host port {} of {} is already in use", port, component_name));
             }
         }
+
+        let mut image = image.clone();
+        let result = image.run().await;
+        image.clean().await;
+        result
+    }
+
+    pub async fn exec(&self, component_name: &str, command: &[String]) -> Result<i32, String> {
+        self.get_image_or_err(component_name)?.exec(command).await
+    }
+
+    /// Opens bash in the component's container, or sh in images without it.
+    pub async fn shell(&self, component_name: &str) -> Result<i32, String> {
+        let command = ["sh", "-c", "if command -v bash >/dev/null 2>&1; then exec bash; else exec sh; fi"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+        self.exec(component_name, &command).await
+    }
+
+    fn docker(&self) -> Result<String, String> {
+        match &self.toolchain {
+            Some(toolchain) => Ok(toolchain.docker().to_string()),
+            None => Err("Toolchain not found".to_string()),
+        }
+    }
+
+    fn absolute_product_directory(&self) -> Result<PathBuf, String> {
+        Path::new(&self.product_directory)
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", self.product_directory, e))
+    }
+
+    /// Volumes cannot be replaced underneath a running container.
+    fn ensure_stopped(&self, mounts: &[Mount]) -> Result<(), String> {
+        let output = std::process::Command::new(self.docker()?)
+            .args(["ps", "--format", "{{.Names}}"])
+            .output()
+            .map_err(|e| format!("Failed to list containers: {}", e))?;
+        let running = String::from_utf8_lossy(&output.stdout).lines().map(|line| line.to_string()).collect::<Vec<_>>();
+
+        let mut busy = self.images.iter()
+            .filter(|image| mounts.iter().any(|mount| mount.component == image.component_name()))
+            .filter(|image| running.contains(&image.container_name()))
+            .map(|image| image.component_name())
+            .collect::<Vec<_>>();
+        busy.dedup();
+
+        if busy.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Stop {} before changing its volumes", busy.join(", ")))
+        }
+    }
+
+    /// Empties the volumes of all components, for `rushd dev --fresh`.
+    pub async fn reset_volumes(&self) -> Result<(), String> {
+        let mounts = Mount::from_images(&self.images, None)?;
+        self.ensure_stopped(&mounts)?;
+        volumes::reset(&self.docker()?, &self.absolute_product_directory()?, &mounts).await
+    }
+
+    pub async fn snapshot_volumes(&self, name: &str, component: Option<&str>) -> Result<(), String> {
+        let mounts = Mount::from_images(&self.images, component)?;
+        self.ensure_stopped(&mounts)?;
+        let snapshots = Snapshots::new(&self.absolute_product_directory()?, &self.docker()?);
+        snapshots.snapshot(name, &mounts).await
     }
 
+    pub async fn restore_volumes(&self, name: &str, component: Option<&str>) -> Result<(), String> {
+        let mounts = Mount::from_images(&self.images, component)?;
+        self.ensure_stopped(&mounts)?;
+        let snapshots = Snapshots::new(&self.absolute_product_directory()?, &self.docker()?);
+        snapshots.restore(name, &mounts).await
+    }
+
+    pub fn list_snapshots(&self) -> Result<Vec<(String, Vec<String>)>, String> {
+        let snapshots = Snapshots::new(&self.absolute_product_directory()?, &self.docker()?);
+        Ok(snapshots.list())
+    }
+
+    /// Writes the dev stack as a Docker Compose file, by default
+    /// `docker-compose.yaml` in the product directory. Returns its path.
+    pub fn export_compose(&self, output: Option<&Path>) -> Result<PathBuf, String> {
+        let product_directory = self.absolute_product_directory()?;
+        let output = match output {
+            Some(output) => output.to_path_buf(),
+            None => product_directory.join("docker-compose.yaml"),
+        };
+        let output_directory = output.parent().ok_or(format!("Invalid output {}", output.display()))?;
+        std::fs::create_dir_all(output_directory).map_err(|e| e.to_string())?;
+        let output_directory = output_directory.canonicalize().map_err(|e| e.to_string())?;
+
+        let compose = compose::render(
+            &self.config,
+            &self.images,
+            &self.services,
+            self.migrations.as_ref(),
+            &product_directory,
+            &output_directory,
+        )?;
+        std::fs::write(&output, compose).map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        Ok(output)
+    }
+
+    /// Removes the dev containers of the product, including those of
+    /// components no longer in the stack.
     pub async fn clean(&self) {
-        for image in &self.images {
-            image.clean().await;
+        let docker = match self.docker() {
+            Ok(docker) => docker,
+            Err(e) => return eprintln!("{}", e),
+        };
+        let labels = [
+            (PRODUCT_LABEL, self.config.product_name()),
+            (ENVIRONMENT_LABEL, self.config.environment()),
+        ];
+        if let Err(e) = remove_labelled_containers(&docker, &labels).await {
+            eprintln!("{}", e);
         }
     }
 }
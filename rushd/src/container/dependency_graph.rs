@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Startup order of the components in a stack. Components are grouped into
+/// waves: every component only depends on components in earlier waves, so
+/// the members of a wave can be started concurrently.
+#[derive(Debug, Clone)]
+pub struct DependencyGraph {
+    waves: Vec<Vec<String>>,
+}
+
+impl DependencyGraph {
+    /// Builds the graph from a map of component names to the names of the
+    /// components they depend on. Fails on unknown dependencies and cycles.
+    pub fn new(dependencies: &BTreeMap<String, Vec<String>>) -> Result<Self, String> {
+        for (component, depends_on) in dependencies {
+            for dependency in depends_on {
+                if !dependencies.contains_key(dependency) {
+                    return Err(format!(
+                        "Component `{}` depends on unknown component `{}`",
+                        component, dependency
+                    ));
+                }
+                if dependency == component {
+                    return Err(format!("Component `{}` depends on itself", component));
+                }
+            }
+        }
+
+        let mut remaining = dependencies
+            .iter()
+            .map(|(component, depends_on)| {
+                (component.clone(), depends_on.iter().cloned().collect::<BTreeSet<_>>())
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut waves = Vec::new();
+        while !remaining.is_empty() {
+            let wave = remaining
+                .iter()
+                .filter(|(_, depends_on)| depends_on.is_empty())
+                .map(|(component, _)| component.clone())
+                .collect::<Vec<_>>();
+
+            if wave.is_empty() {
+                return Err(format!(
+                    "Dependency cycle detected: {}",
+                    Self::find_cycle(&remaining).join(" -> ")
+                ));
+            }
+
+            for component in &wave {
+                remaining.remove(component);
+            }
+            for depends_on in remaining.values_mut() {
+                for component in &wave {
+                    depends_on.remove(component);
+                }
+            }
+            waves.push(wave);
+        }
+
+        Ok(DependencyGraph { waves })
+    }
+
+    /// Follows unresolved dependencies until a component repeats. Every
+    /// remaining component has at least one remaining dependency, so this
+    /// always ends in a cycle.
+    fn find_cycle(remaining: &BTreeMap<String, BTreeSet<String>>) -> Vec<String> {
+        let mut path: Vec<String> = Vec::new();
+        let mut current = match remaining.keys().next() {
+            Some(component) => component.clone(),
+            None => return path,
+        };
+
+        while !path.contains(&current) {
+            path.push(current.clone());
+            current = match remaining.get(&current).and_then(|deps| deps.iter().next()) {
+                Some(next) => next.clone(),
+                None => return path,
+            };
+        }
+
+        let start = path.iter().position(|component| *component == current).unwrap_or(0);
+        let mut cycle = path.split_off(start);
+        cycle.push(current);
+        cycle
+    }
+
+    /// Waves in startup order. Stop them in reverse order.
+    pub fn waves(&self) -> &Vec<Vec<String>> {
+        &self.waves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(dependencies: &[(&str, &[&str])]) -> Result<DependencyGraph, String> {
+        let dependencies = dependencies
+            .iter()
+            .map(|(component, depends_on)| {
+                (component.to_string(), depends_on.iter().map(|d| d.to_string()).collect())
+            })
+            .collect();
+        DependencyGraph::new(&dependencies)
+    }
+
+    #[test]
+    fn groups_components_into_waves() {
+        let graph = graph(&[
+            ("backend", &["database", "cache"]),
+            ("cache", &[]),
+            ("database", &[]),
+            ("frontend", &["backend"]),
+            ("ingress", &["frontend", "backend"]),
+        ])
+        .unwrap();
+        assert_eq!(
+            graph.waves(),
+            &vec![
+                vec!["cache".to_string(), "database".to_string()],
+                vec!["backend".to_string()],
+                vec!["frontend".to_string()],
+                vec!["ingress".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn independent_components_share_a_wave() {
+        let graph = graph(&[("a", &[]), ("b", &[])]).unwrap();
+        assert_eq!(graph.waves(), &vec![vec!["a".to_string(), "b".to_string()]]);
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let error = graph(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"]), ("d", &[])]).unwrap_err();
+        assert_eq!(error, "Dependency cycle detected: a -> b -> c -> a");
+    }
+
+    #[test]
+    fn rejects_self_dependencies() {
+        let error = graph(&[("a", &["a"])]).unwrap_err();
+        assert_eq!(error, "Component `a` depends on itself");
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let error = graph(&[("a", &["b"])]).unwrap_err();
+        assert_eq!(error, "Component `a` depends on unknown component `b`");
+    }
+}
@@ -4,7 +4,7 @@ use std::{
 use tokio::sync::broadcast::{Receiver as BroadcastReceiver};
 
 use colored::Colorize;
-use tokio::process::Command;
+use tokio::process::{Child, Command};
 use super::status::Status;
 use std::sync::Arc;
 use crate::{toolchain::ToolchainContext, utils::DockerCrossCompileGuard};
@@ -12,14 +12,17 @@ use crate::builder::{ComponentBuildSpec};
 use crate::utils::{handle_stream, run_command, run_command_in_window};
 use crate::builder::{BuildContext};
 use crate::builder::BuildType;
-use crate::container::{ServicesSpec, ServiceSpec};
+use crate::container::{discovery_env, LogFilter, LogFormatter, ServicesSpec, ServiceSpec};
+use crate::container::container_reactor::NATIVE_HOST;
 use std::collections::HashMap;
 use crate::Directory;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use core::cell::RefCell;
 use std::rc::Rc;
 use crate::builder::Config;
+use crate::certs::DevCertificates;
+use crate::control::LogLine;
 
 impl TryInto<DockerImage> for Arc<Mutex<ComponentBuildSpec>> {
     type Error = String;
@@ -28,6 +31,38 @@ impl TryInto<DockerImage> for Arc<Mutex<ComponentBuildSpec>> {
     }
 }
 
+/// Labels set on the containers started by `rushd dev`. Cleanup only
+/// touches containers carrying them, so other products are left alone.
+pub const PRODUCT_LABEL: &str = "rushd.product";
+pub const ENVIRONMENT_LABEL: &str = "rushd.environment";
+pub const COMPONENT_LABEL: &str = "rushd.component";
+
+/// Where the dev certificate and key are mounted in an ingress that serves
+/// HTTPS. They are never copied into the image, which may be pushed.
+pub const DEV_CERTIFICATES_PATH: &str = "/etc/rushd/certs";
+
+/// Removes the stopped containers carrying all of the given labels.
+pub async fn remove_labelled_containers(docker: &str, labels: &[(&str, &str)]) -> Result<(), String> {
+    let mut args = vec!["ps".to_string(), "-aq".to_string()];
+    for (key, value) in labels {
+        args.push("--filter".to_string());
+        args.push(format!("label={}={}", key, value));
+    }
+    let output = Command::new(docker)
+        .args(&args)
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let ids = String::from_utf8_lossy(&output.stdout).split_whitespace().map(|id| id.to_string()).collect::<Vec<_>>();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let mut args = vec!["rm"];
+    args.extend(ids.iter().map(|id| id.as_str()));
+    run_command("clean".white().bold(), docker, args).await
+}
+
 #[derive(Debug, Clone)]
 pub struct DockerImage {
     
@@ -47,7 +82,9 @@ pub struct DockerImage {
     config: Arc<Config>,
     spec: Arc<Mutex<ComponentBuildSpec>>,
     toolchain: Option<Arc<ToolchainContext>>,
-    network_name: Option<String>
+    network_name: Option<String>,
+    log_filter: LogFilter,
+    log_sender: Option<tokio::sync::broadcast::Sender<LogLine>>,
 }
 
 
@@ -62,6 +99,15 @@ impl DockerImage {
         self.network_name = Some(network_name);
     }
 
+    pub fn set_log_filter(&mut self, log_filter: LogFilter) {
+        self.log_filter = log_filter;
+    }
+
+    /// Output lines are also published here, for `rushd ctl logs`
+    pub fn set_log_sender(&mut self, log_sender: tokio::sync::broadcast::Sender<LogLine>) {
+        self.log_sender = Some(log_sender);
+    }
+
     pub fn create_cross_compile_guard(build_type: &BuildType, toolchain: &ToolchainContext) -> DockerCrossCompileGuard {
         let target = match build_type {
             BuildType::PureDockerImage{ .. } => toolchain.host(),
@@ -79,7 +125,7 @@ impl DockerImage {
             BuildType::TrunkWasm{ dockerfile_path, context_dir, .. } => (Some(dockerfile_path.clone()), context_dir.clone()),
             BuildType::RustBinary{ dockerfile_path, context_dir,.. } => (Some(dockerfile_path.clone()), context_dir.clone()),
             BuildType::Script{ dockerfile_path, context_dir,.. } => (Some(dockerfile_path.clone()), context_dir.clone()),
-            BuildType::Ingress{ dockerfile_path, context_dir, ..} => (Some(dockerfile_path.clone()), context_dir.clone()),
+            BuildType::Ingress{ dockerfile_path, context_dir, ..} if !spec.is_builtin_ingress() => (Some(dockerfile_path.clone()), context_dir.clone()),
             _ => (None, None)        
         };
 
@@ -132,9 +178,15 @@ impl DockerImage {
             _ => (format!("{}-{}", spec.product_name, spec.component_name), None),
         };
 
-        let product_name = spec.product_name.clone();
-        let depends_on = spec.depends_on.iter().map(move |s| 
-            format!("{}-{}", product_name, s)).collect::<Vec<String>>();
+        // An ingress needs the components it routes to
+        let mut depends_on = spec.depends_on.clone();
+        if let BuildType::Ingress{ components, .. } = &spec.build_type {
+            for component in components {
+                if !depends_on.contains(component) {
+                    depends_on.push(component.clone());
+                }
+            }
+        }
         Ok(DockerImage {
             image_name,
             repo: None, // Assuming repo is not part of ComponentBuildSpec and defaults to None
@@ -147,7 +199,9 @@ impl DockerImage {
             port,
             target_port,
             toolchain: None,
-            network_name: None
+            network_name: None,
+            log_filter: LogFilter::default(),
+            log_sender: None,
         })
     }
 
@@ -162,11 +216,11 @@ impl DockerImage {
     pub fn set_port(&mut self, port: u16) {
         self.port = Some(port);
     }
-    /*
+
     pub fn set_target_port(&mut self, target_port: u16) {
         self.target_port = Some(target_port);
     }
-
+    /*
     pub fn set_color(&mut self, color: String) {
         self.spec.color = color;
     }
@@ -221,6 +275,13 @@ impl DockerImage {
         self.spec.lock().unwrap().component_name.clone()
     }
 
+    /// Name of the container started for the component by `rushd dev`,
+    /// scoped by product and environment. Other components reach it by its
+    /// component name, which is its alias on the product network.
+    pub fn container_name(&self) -> String {
+        format!("{}-{}-{}", self.config.product_uri(), self.config.environment(), self.component_name())
+    }
+
     pub fn identifier(&self) -> String {
         match &self.repo {
             Some(r) => format!("{}/{}", r, self.tagged_image_name()),
@@ -263,45 +324,13 @@ impl DockerImage {
 
             //task.clean().await;
             let _ = status_sender.send(Status::InProgress);
-            let mut args = vec!["run".to_string(), "--name".to_string(), spec.component_name.clone(), "--network".to_string(), network_name];
-            
-            if let Some(entrypoint) = entrypoint {
-                args.push("--entrypoint".to_string());
-                args.push(entrypoint.clone());
-            }            
-            if let Some(port) = task.port {
-                if let Some(target_port) = task.target_port {
-                    args.push("-p".to_string());
-                    args.push(format!("{}:{}", port, target_port));
-                }
-            }
-
-            if let Some(env_vars) = &spec.env {
-                for (key, value) in env_vars {
-                    args.push("-e".to_string());
-                    args.push(format!("{}={}", key, value));
-                }
-            }
-
-            if let Some(volumes) = &spec.volumes {
-                for (host_path, container_path) in volumes {
-                    args.push("-v".to_string());
-                    args.push(format!("{}:{}", host_path, container_path));
-                }
-            }
-
-            for arg in &spec.docker_extra_run_args {
-                args.push(arg.clone());
-            }            
-
-            args.push(task.tagged_image_name());
-            if let Some(command) = command {
-                args.push(command.clone());
-            }
+            let mut command = if spec.dev_mode.is_native() {
+                task.native_command(&spec, &toolchain)
+            } else {
+                task.docker_run_command(&spec, &toolchain, network_name, command, entrypoint)
+            };
 
-            println!("Running docker for {}: {}", spec.component_name, args.join(" "));
-            let mut child_process_result = Command::new(toolchain.docker())
-                .args(args)
+            let mut child_process_result = command
                 .stdout(std::process::Stdio::piped())
                 .stderr(std::process::Stdio::piped())
                 .spawn();
@@ -320,13 +349,21 @@ impl DockerImage {
                     let lines = Arc::new(Mutex::new(Vec::new()));
                     let lines_clone = lines.clone();
                     let formatted_label_clone = formatted_label.clone();
+                    let log_formatter = LogFormatter::new(spec.log_format, task.log_filter.clone());
+                    let log_sender = task.log_sender.clone();
+                    let component_name = spec.component_name.clone();
 
                     tokio::spawn(async move {
                         while let Ok(line) = rx.recv() {
                             let mut lines = lines_clone.lock().unwrap();
                             lines.push(line.trim_end().to_string());
                             let clean_line = line.trim_end().replace("\r", "").replace("\n",""); // .replace("\x1B", "")
-                            println!("{} |   {}", formatted_label_clone, clean_line);
+                            if let Some(log_sender) = &log_sender {
+                                let _ = log_sender.send(LogLine { component: component_name.clone(), line: clean_line.clone() });
+                            }
+                            if let Some(formatted_line) = log_formatter.format(&clean_line) {
+                                println!("{} |   {}", formatted_label_clone, formatted_line);
+                            }
                         }
                     });
 
@@ -335,16 +372,22 @@ impl DockerImage {
 
                         }
                         _ =  terminate_receiver.recv() => {
-                            // TODO: See you can find something more cross-platform friendly
-                            let mut kill = Command::new("kill")
-                            .args(["-s", "TERM", &child.id().unwrap().to_string()])
-                            .spawn().expect("Failed to kill process");
-                        kill.wait().await.unwrap();
-                            let _ = child.kill();
-                            let _ = status_sender.send(Status::Terminate);                                
+                            let stopped = if spec.dev_mode.is_native() {
+                                task.stop_native(child, &spec).await
+                            } else {
+                                task.stop_container(child, &spec, &toolchain).await
+                            };
+                            match stopped {
+                                Ok(true) => (),
+                                Ok(false) => {
+                                    let message = format!("Did not exit within {}s of SIG{} and was killed", spec.stop_timeout, spec.stop_signal);
+                                    println!("{} |   {}", formatted_label, message.red().bold());
+                                }
+                                Err(e) => eprintln!("{} |   Failed to stop: {}", formatted_label, e),
+                            }
+                            let _ = status_sender.send(Status::Terminate);
                         }
-                    }                        
-
+                    }
 
                     if let Some(code) = child.wait().await.unwrap().code() {
                         let message = format!("Process exited with code: {}", code);
@@ -369,13 +412,258 @@ impl DockerImage {
         })
     }
 
+    fn docker_run_command(&self, spec: &ComponentBuildSpec, toolchain: &ToolchainContext, network_name: String, command: Option<String>, entrypoint: Option<String>) -> Command {
+        use std::os::unix::process::CommandExt;
+
+        let mut args = vec![
+            "run".to_string(),
+            "--name".to_string(), self.container_name(),
+            "--network".to_string(), network_name,
+            "--network-alias".to_string(), spec.component_name.clone(),
+        ];
+        for (key, value) in [
+            (PRODUCT_LABEL, self.config.product_name()),
+            (ENVIRONMENT_LABEL, self.config.environment()),
+            (COMPONENT_LABEL, spec.component_name.as_str()),
+        ] {
+            args.push("--label".to_string());
+            args.push(format!("{}={}", key, value));
+        }
+
+        // Allows reaching components that run natively on the host
+        args.push("--add-host".to_string());
+        args.push(format!("{}:host-gateway", NATIVE_HOST));
+
+        // `docker stop` sends these when the component is terminated
+        args.push("--stop-signal".to_string());
+        args.push(format!("SIG{}", spec.stop_signal));
+        args.push("--stop-timeout".to_string());
+        args.push(spec.stop_timeout.to_string());
+        args.extend(spec.resources.docker_args());
+
+        if let Some(entrypoint) = entrypoint {
+            args.push("--entrypoint".to_string());
+            args.push(entrypoint.clone());
+        }            
+        if let Some(port) = self.port {
+            if let Some(target_port) = self.target_port {
+                args.push("-p".to_string());
+                args.push(format!("{}:{}", port, target_port));
+            }
+        }
+
+        if let Some(https_port) = spec.https_port {
+            args.push("-p".to_string());
+            args.push(format!("{}:443", https_port));
+        }
+
+        // The component's own env takes precedence over discovery
+        if let Some(services) = &spec.services {
+            let discovery = discovery_env(services, |service| (service.host.clone(), service.target_port));
+            let overridden = |key: &String| spec.env.as_ref().is_some_and(|env| env.contains_key(key));
+            for (key, value) in discovery.into_iter().filter(|(key, _)| !overridden(key)) {
+                args.push("-e".to_string());
+                args.push(format!("{}={}", key, value));
+            }
+        }
+
+        if let Some(env_vars) = &spec.env {
+            for (key, value) in env_vars {
+                args.push("-e".to_string());
+                args.push(format!("{}={}", key, value));
+            }
+        }
+
+        if let Some(volumes) = &spec.volumes {
+            for (host_path, container_path) in volumes {
+                args.push("-v".to_string());
+                args.push(format!("{}:{}", host_path, container_path));
+            }
+        }
+
+        for (host_path, container_path) in self.dev_certificate_mounts() {
+            args.push("-v".to_string());
+            args.push(format!("{}:{}:ro", host_path.display(), container_path));
+        }
+
+        for arg in &spec.docker_extra_run_args {
+            args.push(arg.clone());
+        }            
+
+        args.push(self.tagged_image_name());
+        if let Some(command) = command {
+            args.push(command.clone());
+        }
+
+        println!("Running docker for {}: {}", spec.component_name, args.join(" "));
+        let mut command = std::process::Command::new(toolchain.docker());
+        command.args(args);
+        // Keeps Ctrl+C from reaching the container through the docker client,
+        // it is stopped with `docker stop` instead
+        command.process_group(0);
+        Command::from(command)
+    }
+
+    /// Stops the container with `docker stop`, which sends the stop signal
+    /// and kills the container once the stop timeout has passed. Returns
+    /// `false` when the container had to be killed.
+    async fn stop_container(&self, child: &mut Child, spec: &ComponentBuildSpec, toolchain: &ToolchainContext) -> Result<bool, String> {
+        let container_name = self.container_name();
+        let timeout = spec.stop_timeout.to_string();
+        let output = Command::new(toolchain.docker())
+            .args(["stop", "--time", &timeout, &container_name])
+            .output()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            // The container was never created, for instance while its image
+            // was still being pulled
+            let _ = child.kill().await;
+            return Ok(true);
+        }
+        let _ = child.wait().await;
+
+        let output = Command::new(toolchain.docker())
+            .args(["inspect", "--format", "{{.State.ExitCode}}", &container_name])
+            .output()
+            .await
+            .map_err(|e| e.to_string())?;
+        let exit_code = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(exit_code != "137")
+    }
+
+    /// Sends the stop signal to the process group of a native component and
+    /// kills the group if it has not exited within the stop timeout. Returns
+    /// `false` when it had to be killed.
+    async fn stop_native(&self, child: &mut Child, spec: &ComponentBuildSpec) -> Result<bool, String> {
+        // Native processes run in their own process group so that the
+        // processes spawned by cargo are stopped as well.
+        let group = match child.id() {
+            Some(pid) => format!("-{}", pid),
+            None => return Ok(true),
+        };
+        Command::new("kill")
+            .args(["-s", &spec.stop_signal, "--", &group])
+            .status()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let timeout = tokio::time::Duration::from_secs(spec.stop_timeout);
+        if tokio::time::timeout(timeout, child.wait()).await.is_ok() {
+            return Ok(true);
+        }
+
+        Command::new("kill")
+            .args(["-s", "KILL", "--", &group])
+            .status()
+            .await
+            .map_err(|e| e.to_string())?;
+        let _ = child.wait().await;
+        Ok(false)
+    }
+
+    fn native_location(&self, spec: &ComponentBuildSpec) -> PathBuf {
+        let location = match &spec.build_type {
+            BuildType::RustBinary{ location, .. } => location.clone(),
+            BuildType::TrunkWasm{ location, .. } => location.clone(),
+            _ => panic!("{} cannot run natively", spec.component_name),
+        };
+        Path::new(self.config.root_path()).join(self.config.product_path()).join(location)
+    }
+
+    fn native_command(&self, spec: &ComponentBuildSpec, toolchain: &ToolchainContext) -> Command {
+        use std::os::unix::process::CommandExt;
+
+        let location = self.native_location(spec);
+        let mut command = match &spec.build_type {
+            BuildType::TrunkWasm{ .. } => {
+                let port = self.port.expect("No port assigned to trunk serve").to_string();
+                let public_url = spec.mount_point.clone().unwrap_or("/".to_string());
+                let public_url = if public_url.ends_with('/') { public_url } else { format!("{}/", public_url) };
+
+                let mut command = std::process::Command::new(toolchain.trunk());
+                command.args(["serve", "--address", "0.0.0.0", "--port", &port, "--public-url", &public_url]);
+                command
+            }
+            _ => {
+                let mut command = std::process::Command::new("cargo");
+                command.arg("run");
+                command
+            }
+        };
+        command.current_dir(&location);
+
+        // Processes on the host reach every component on its host port
+        if let Some(services) = &spec.services {
+            command.envs(discovery_env(services, |service| ("localhost".to_string(), service.port)));
+        }
+        if let Some(env_vars) = &spec.env {
+            command.envs(env_vars);
+        }
+        if let Some(port) = self.port {
+            command.env("PORT", port.to_string());
+        }
+        command.process_group(0);
+
+        let program = command.get_program().to_string_lossy().to_string();
+        let args = command.get_args().map(|arg| arg.to_string_lossy().to_string()).collect::<Vec<_>>();
+        println!("Running {} natively in {}: {} {}", spec.component_name, location.display(), program, args.join(" "));
+        Command::from(command)
+    }
+
+    fn absolute_product_path(&self) -> PathBuf {
+        Path::new(self.config.root_path()).join(self.config.product_path().trim_start_matches("./"))
+    }
+
+    fn ensure_dev_certificates(&self, ctx: &BuildContext) -> Result<(), String> {
+        let mut hosts = vec!["localhost".to_string(), "*.localhost".to_string(), "127.0.0.1".to_string()];
+        for subdomain in &ctx.subdomains {
+            hosts.push(format!("{}.localhost", subdomain));
+        }
+
+        DevCertificates::ensure(Path::new(self.config.root_path()), &self.absolute_product_path(), &hosts)?;
+        Ok(())
+    }
+
+    /// Host files of the dev certificate and key and where they are mounted,
+    /// for an ingress that serves HTTPS in dev.
+    pub fn dev_certificate_mounts(&self) -> Vec<(PathBuf, String)> {
+        if !self.spec.lock().unwrap().serves_dev_tls() {
+            return Vec::new();
+        }
+        let directory = DevCertificates::directory(&self.absolute_product_path());
+        ["tls.crt", "tls.key"]
+            .iter()
+            .map(|file| (directory.join(file), format!("{}/{}", DEV_CERTIFICATES_PATH, file)))
+            .collect()
+    }
+
+    /// Compiles a component that runs natively in dev mode. Returns `false`
+    /// for components that are run in Docker.
+    pub async fn build_native(&self) -> Result<bool, String> {
+        let spec = self.spec.lock().unwrap().clone();
+        if !spec.dev_mode.is_native() {
+            return Ok(false);
+        }
+
+        // `trunk serve` builds the frontend itself once launched
+        if let BuildType::TrunkWasm{ .. } = spec.build_type {
+            return Ok(true);
+        }
+
+        let location = self.native_location(&spec);
+        let _dir_raii = Directory::chpath(&location);
+        run_command_in_window(10, "cargo", "cargo", vec!["build"]).await?;
+        Ok(true)
+    }
+
     pub async fn kill(&self) {
         let toolchain = match &self.toolchain {
             Some(toolchain) => toolchain.clone(),
             None => panic!("Cannot launch docker image without a toolchain"),
         };
-        let component_name = self.spec.lock().unwrap().component_name.clone();
-        let _ = run_command("kill".white().bold(), toolchain.docker(), vec!["kill", &component_name]).await;
+        let container_name = self.container_name();
+        let _ = run_command("kill".white().bold(), toolchain.docker(), vec!["kill", &container_name]).await;
     }
 
 
@@ -384,10 +672,13 @@ impl DockerImage {
             Some(toolchain) => toolchain.clone(),
             None => panic!("Cannot launch docker image without a toolchain"),
         };
-        let component_name = self.spec.lock().unwrap().component_name.clone();
-        let args = vec!["rm", &component_name];
-
-        let _ = run_command("clean".white().bold(), toolchain.docker(), args.into()).await;
+        let component_name = self.component_name();
+        let labels = [
+            (PRODUCT_LABEL, self.config.product_name()),
+            (ENVIRONMENT_LABEL, self.config.environment()),
+            (COMPONENT_LABEL, component_name.as_str()),
+        ];
+        let _ = remove_labelled_containers(toolchain.docker(), &labels).await;
 
         // TODO: Remove artefacts
     }
@@ -397,39 +688,57 @@ impl DockerImage {
         self.clean().await;
     }
 
-    pub async fn run(&self) -> Result<(), String> {
-        self.build().await?;
+    /// Builds the component and runs it on its own, the way `rushd dev`
+    /// would, until it exits or Ctrl+C is pressed.
+    pub async fn run(&mut self) -> Result<(), String> {
+        if !self.build_native().await? {
+            self.build().await?;
+        }
+
+        let (status_sender, _status_receiver) = mpsc::channel();
+        let (terminate_sender, terminate_receiver) = tokio::sync::broadcast::channel(1);
+        let label_length = self.component_name().len();
+        let mut handle = self.launch(label_length, terminate_receiver, status_sender);
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                let _ = terminate_sender.send(());
+                let _ = (&mut handle).await;
+            }
+            _ = &mut handle => {}
+        }
+        Ok(())
+    }
 
+    /// Runs a command in the running dev container of the component with the
+    /// terminal attached. Returns the exit code of the command.
+    pub async fn exec(&self, command: &[String]) -> Result<i32, String> {
+        use std::io::IsTerminal;
 
         let toolchain = match &self.toolchain {
             Some(toolchain) => toolchain.clone(),
             None => panic!("Cannot launch docker image without a toolchain"),
-        };        
-
-        let env_guard = DockerImage::create_cross_compile_guard(&self.spec.lock().unwrap().build_type, &toolchain);
-
-        let formatted_label = format!("{} [{}]", 
-            self.spec.lock().unwrap().component_name.to_string(),
-            env_guard.target()
-        );
-        let formatted_label =formatted_label.white().bold();
-        let extra_args = self.spec.lock().unwrap().docker_extra_run_args.clone();
-
-        // TODO: Get ports
-        let image_name = self.tagged_image_name();
-        let mut args = vec!["run", "-p", "8000:80", &image_name];
-        for arg in &extra_args {
-            args.push(&arg);
+        };
+        let spec = self.spec.lock().unwrap().clone();
+        if spec.dev_mode.is_native() {
+            return Err(format!("{} runs natively on the host, there is no container to enter", spec.component_name));
         }
 
-        match run_command(formatted_label, toolchain.docker(), args).await {
-            Ok(_) => {
-                Ok(())
-            }
-            Err(e) => {
-                Err(e)
-            }
+        let mut args = vec!["exec".to_string()];
+        if std::io::stdin().is_terminal() {
+            args.push("-it".to_string());
+        } else {
+            args.push("-i".to_string());
         }
+        args.push(self.container_name());
+        args.extend(command.iter().cloned());
+
+        let status = Command::new(toolchain.docker())
+            .args(&args)
+            .status()
+            .await
+            .map_err(|e| format!("Failed to run docker exec: {}", e))?;
+        Ok(status.code().unwrap_or(1))
     }
 
     pub async fn push(&self) -> Result<(), String> {
@@ -479,7 +788,10 @@ impl DockerImage {
         };
         let spec = self.spec.lock().unwrap().clone();
 
-
+        // Routed by rushd itself, there is no image to build
+        if spec.is_builtin_ingress() {
+            return Ok(());
+        }
 
         let dockerfile_path = match &spec.build_type {
             BuildType::TrunkWasm{ dockerfile_path, .. } => dockerfile_path.clone(),
@@ -513,6 +825,10 @@ impl DockerImage {
             }        
         }
 
+        if ctx.tls {
+            self.ensure_dev_certificates(&ctx)?;
+        }
+
         // Cross compiling if needed
         if let Some(build_command) = &self.build_script(&ctx) {
             match run_command_in_window(10, "build", "sh", vec!["-c", build_command]).await {
@@ -541,3 +857,38 @@ impl DockerImage {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// The example ingress must read the certificates from where
+    /// `dev_certificate_mounts` puts them, in every server block.
+    #[test]
+    fn example_ingress_uses_mounted_certificates() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../products/helloworld.com/nginx.ingress.conf");
+        let template = std::fs::read_to_string(&path).unwrap();
+        let context = tera::Context::from_serialize(json!({
+            "tls": true,
+            "domain": "helloworld.com",
+            "subdomains": ["docs"],
+            "services": {
+                "backend": { "host": "backend", "target_port": 8000, "mount_point": "/api", "subdomain": null },
+                "docs": { "host": "docs", "target_port": 8000, "mount_point": null, "subdomain": "docs" },
+            },
+        }))
+        .unwrap();
+        let rendered = tera::Tera::one_off(&template, &context, false).unwrap();
+
+        let certificates = rendered
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| line.starts_with("ssl_certificate"))
+            .collect::<Vec<_>>();
+        assert_eq!(certificates.len(), 4);
+        for line in certificates {
+            assert!(line.contains(&format!("{}/tls.", DEV_CERTIFICATES_PATH)), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use colored::Colorize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level.trim().to_lowercase().as_str() {
+            "trace" => Some(Self::Trace),
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "error" | "err" | "fatal" | "critical" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> colored::ColoredString {
+        match self {
+            LogLevel::Trace => "TRACE".magenta(),
+            LogLevel::Debug => "DEBUG".blue(),
+            LogLevel::Info => " INFO".green(),
+            LogLevel::Warn => " WARN".yellow().bold(),
+            LogLevel::Error => "ERROR".red().bold(),
+        }
+    }
+}
+
+/// How the output of a component should be interpreted. `Auto` renders
+/// lines that parse as JSON objects and passes everything else through,
+/// `Text` prints every line as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Auto,
+    Text,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.trim().to_lowercase().as_str() {
+            "auto" | "json" => Some(Self::Auto),
+            "text" | "plain" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+/// Filters applied to structured log lines. Plain-text lines are never filtered.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    min_level: Option<LogLevel>,
+    fields: Vec<(String, String)>,
+}
+
+impl LogFilter {
+    pub fn new(min_level: Option<&str>, fields: Vec<&str>) -> Result<Self, String> {
+        let min_level = match min_level {
+            Some(level) => match LogLevel::parse(level) {
+                Some(level) => Some(level),
+                None => return Err(format!("Invalid log level: {}", level)),
+            },
+            None => None,
+        };
+
+        let fields = fields
+            .into_iter()
+            .map(|field| match field.split_once('=') {
+                Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+                None => Err(format!("Invalid log filter `{}`, expected KEY=VALUE", field)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(LogFilter { min_level, fields })
+    }
+
+    fn accepts(&self, entry: &JsonLogEntry) -> bool {
+        if let Some(min_level) = self.min_level {
+            match entry.level {
+                Some(level) if level < min_level => return false,
+                _ => (),
+            }
+        }
+
+        self.fields.iter().all(|(key, value)| {
+            entry
+                .lookup(key)
+                .is_some_and(|v| value_to_string(v) == *value)
+        })
+    }
+}
+
+struct JsonLogEntry {
+    level: Option<LogLevel>,
+    target: Option<String>,
+    message: Option<String>,
+    fields: Vec<(String, Value)>,
+    raw: Map<String, Value>,
+}
+
+impl JsonLogEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('{') {
+            return None;
+        }
+
+        let raw = match serde_json::from_str::<Value>(trimmed) {
+            Ok(Value::Object(map)) => map,
+            _ => return None,
+        };
+
+        let level = ["level", "severity", "lvl"]
+            .iter()
+            .find_map(|key| raw.get(*key).and_then(Value::as_str))
+            .and_then(LogLevel::parse);
+        let target = ["target", "logger", "module"]
+            .iter()
+            .find_map(|key| raw.get(*key).and_then(Value::as_str))
+            .map(|s| s.to_string());
+
+        // tracing-subscriber nests the message and user fields under `fields`,
+        // most other JSON loggers keep them at the top level.
+        let mut message = None;
+        let mut fields = Vec::new();
+        let skipped = ["level", "severity", "lvl", "target", "logger", "module", "timestamp", "time", "ts"];
+        for (key, value) in &raw {
+            if skipped.contains(&key.as_str()) {
+                continue;
+            }
+            match (key.as_str(), value) {
+                ("fields", Value::Object(nested)) => {
+                    for (key, value) in nested {
+                        if key == "message" {
+                            message = Some(value_to_string(value));
+                        } else {
+                            fields.push((key.clone(), value.clone()));
+                        }
+                    }
+                }
+                ("message" | "msg", value) => message = Some(value_to_string(value)),
+                _ => fields.push((key.clone(), value.clone())),
+            }
+        }
+
+        Some(JsonLogEntry {
+            level,
+            target,
+            message,
+            fields,
+            raw,
+        })
+    }
+
+    fn lookup(&self, key: &str) -> Option<&Value> {
+        if let Some(value) = self.raw.get(key) {
+            return Some(value);
+        }
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .or_else(|| {
+                self.raw
+                    .get("span")
+                    .and_then(|span| span.get(key))
+            })
+    }
+
+    fn render(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(level) = self.level {
+            parts.push(level.label().to_string());
+        }
+        if let Some(target) = &self.target {
+            parts.push(format!("{}:", target).dimmed().to_string());
+        }
+        if let Some(message) = &self.message {
+            parts.push(message.clone());
+        }
+        for (key, value) in &self.fields {
+            parts.push(format!("{}={}", key.italic(), value_to_string(value)));
+        }
+        parts.join(" ")
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Renders the output lines of a single component according to its
+/// `log_format` and the filter given on the command line.
+#[derive(Debug, Clone)]
+pub struct LogFormatter {
+    format: LogFormat,
+    filter: LogFilter,
+}
+
+impl LogFormatter {
+    pub fn new(format: LogFormat, filter: LogFilter) -> Self {
+        LogFormatter { format, filter }
+    }
+
+    /// Returns the line to print or `None` if the filter rejects it.
+    pub fn format(&self, line: &str) -> Option<String> {
+        let entry = match self.format {
+            LogFormat::Text => None,
+            LogFormat::Auto => JsonLogEntry::parse(line),
+        };
+
+        match entry {
+            Some(entry) => {
+                if self.filter.accepts(&entry) {
+                    Some(entry.render())
+                } else {
+                    None
+                }
+            }
+            None => Some(line.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(line: &str) -> Option<String> {
+        colored::control::set_override(false);
+        LogFormatter::new(LogFormat::Auto, LogFilter::default()).format(line)
+    }
+
+    #[test]
+    fn renders_json_lines() {
+        assert_eq!(
+            format(r#"{"level":"warn","target":"api","msg":"slow request","ms":250}"#).unwrap(),
+            " WARN api: slow request ms=250"
+        );
+    }
+
+    #[test]
+    fn renders_tracing_subscriber_lines() {
+        assert_eq!(
+            format(r#"{"timestamp":"2024-01-01T00:00:00Z","level":"INFO","fields":{"message":"started","port":8000},"target":"backend"}"#).unwrap(),
+            " INFO backend: started port=8000"
+        );
+    }
+
+    #[test]
+    fn passes_through_other_lines() {
+        for line in ["plain text", "{not json", "[1, 2]", r#"{"level":"info""#] {
+            assert_eq!(format(line).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn text_format_never_parses() {
+        let line = r#"{"level":"info","msg":"hello"}"#;
+        let formatter = LogFormatter::new(LogFormat::Text, LogFilter::default());
+        assert_eq!(formatter.format(line).unwrap(), line);
+    }
+
+    #[test]
+    fn renders_lines_without_level_or_message() {
+        assert_eq!(format(r#"{"msg":"no level"}"#).unwrap(), "no level");
+        assert_eq!(format(r#"{"level":"error","code":7}"#).unwrap(), "ERROR code=7");
+        assert_eq!(format("{}").unwrap(), "");
+    }
+
+    #[test]
+    fn filters_structured_lines_only() {
+        colored::control::set_override(false);
+        let filter = LogFilter::new(Some("warn"), vec!["user=alice"]).unwrap();
+        let formatter = LogFormatter::new(LogFormat::Auto, filter);
+        assert!(formatter.format(r#"{"level":"info","user":"alice"}"#).is_none());
+        assert!(formatter.format(r#"{"level":"error","user":"bob"}"#).is_none());
+        assert!(formatter.format(r#"{"user":"alice"}"#).is_some());
+        assert!(formatter.format(r#"{"level":"error","user":"alice"}"#).is_some());
+        assert_eq!(formatter.format("plain text").unwrap(), "plain text");
+    }
+}
@@ -0,0 +1,344 @@
+use crate::builder::Config;
+use crate::container::compose;
+use crate::container::docker::DockerImage;
+use crate::utils::run_command;
+use colored::Colorize;
+use serde_json::json;
+use serde_yaml::Mapping;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the stack.yaml section holding the migrations settings. It is
+/// not a component.
+pub const MIGRATIONS_KEY: &str = "migrations";
+
+/// Directory under the rendered manifests holding the migration Job. It
+/// sorts before the component directories, which are prefixed with their
+/// priority.
+pub const MIGRATIONS_MANIFEST_DIR: &str = "00_migrations";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` gives the same Job name
+/// across Rust releases.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+const MOUNT_PATH: &str = "/migrations";
+const URL_VARIABLE: &str = "MIGRATIONS_DATABASE_URL";
+
+/// Waits for the database, then applies every `.sql` file that is not yet
+/// recorded in `_rushd_migrations`, each in its own transaction together
+/// with its record.
+const MIGRATE_SCRIPT: &str = r#"set -e
+for i in $(seq 1 60); do
+  if psql "$MIGRATIONS_DATABASE_URL" -c 'SELECT 1' >/dev/null 2>&1; then break; fi
+  if [ "$i" = 60 ]; then echo "Database did not become ready"; exit 1; fi
+  sleep 1
+done
+psql "$MIGRATIONS_DATABASE_URL" -q -v ON_ERROR_STOP=1 -c 'SET client_min_messages = warning; CREATE TABLE IF NOT EXISTS _rushd_migrations (name TEXT PRIMARY KEY, applied_at TIMESTAMPTZ NOT NULL DEFAULT now())'
+for file in $(ls /migrations/*.sql 2>/dev/null | sort); do
+  name=$(basename "$file")
+  applied=$(psql "$MIGRATIONS_DATABASE_URL" -tA -c "SELECT 1 FROM _rushd_migrations WHERE name = '$name'")
+  if [ "$applied" = "1" ]; then continue; fi
+  echo "Applying $name"
+  psql "$MIGRATIONS_DATABASE_URL" -q -v ON_ERROR_STOP=1 --single-transaction -f "$file" -c "INSERT INTO _rushd_migrations (name) VALUES ('$name')"
+done
+echo "Migrations are up to date"
+"#;
+
+/// SQL migrations applied to one of the stack's database components.
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    /// Directory with the `.sql` files, relative to the product directory.
+    /// Files are applied in lexical order.
+    directory: String,
+    /// Component running the database
+    database: String,
+    /// Environment variable holding the connection URL
+    connection_variable: String,
+    /// Component whose env defines `connection_variable`, if any
+    connection_component: Option<String>,
+}
+
+impl Migrations {
+    pub fn from_yaml(yaml_section: &serde_yaml::Value) -> Result<Self, String> {
+        let field = |name: &str| {
+            yaml_section
+                .get(name)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .ok_or(format!("`{}` is required in the migrations section", name))
+        };
+
+        Ok(Migrations {
+            directory: field("directory")?,
+            database: field("database")?,
+            connection_variable: field("connection_variable")?,
+            connection_component: field("connection_component").ok(),
+        })
+    }
+
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    pub fn connection_component(&self) -> Option<&str> {
+        self.connection_component.as_deref()
+    }
+
+    pub fn directory(&self) -> &str {
+        &self.directory
+    }
+
+    fn files(&self, product_directory: &Path) -> Result<Vec<PathBuf>, String> {
+        let directory = product_directory.join(&self.directory);
+        let mut files = std::fs::read_dir(&directory)
+            .map_err(|e| format!("Failed to read migrations in {}: {}", directory.display(), e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
+            .collect::<Vec<_>>();
+        files.sort();
+        Ok(files)
+    }
+
+    /// The connection URL: the value of `connection_variable` in rushd's
+    /// environment or in the env of `connection_component`.
+    fn configured_url(&self, images: &[DockerImage]) -> Option<String> {
+        if let Ok(url) = std::env::var(&self.connection_variable) {
+            return Some(url);
+        }
+
+        let component = self.connection_component.as_ref()?;
+        images
+            .iter()
+            .find(|image| image.component_name() == *component)
+            .and_then(|image| image.spec().env)
+            .and_then(|env| env.get(&self.connection_variable).cloned())
+    }
+
+    /// In dev the migrations run in a container on the stack's network, so
+    /// the database is addressed by its component name. The URL is derived
+    /// from the Postgres settings of the database component when possible.
+    fn dev_url(&self, database: &DockerImage, images: &[DockerImage]) -> Option<String> {
+        let env = database.spec().env.unwrap_or_default();
+        match env.get("POSTGRES_PASSWORD") {
+            Some(password) => {
+                let user = env.get("POSTGRES_USER").cloned().unwrap_or("postgres".to_string());
+                let db = env.get("POSTGRES_DB").cloned().unwrap_or(user.clone());
+                let port = database.target_port().unwrap_or(5432);
+                Some(format!("postgres://{}:{}@{}:{}/{}", user, password, self.database, port, db))
+            }
+            None => self.configured_url(images),
+        }
+    }
+
+    /// Applies the pending migrations against the dev database. The image of
+    /// the database component provides `psql`.
+    pub async fn run_dev(&self, product_directory: &Path, docker: &str, network_name: &str, images: &[DockerImage]) -> Result<(), String> {
+        let database = images
+            .iter()
+            .find(|image| image.component_name() == self.database)
+            .ok_or(format!("Migrations target unknown component `{}`", self.database))?;
+        let url = self.dev_url(database, images).ok_or(format!(
+            "No connection URL for the migrations: set {} or POSTGRES_PASSWORD on `{}`",
+            self.connection_variable, self.database
+        ))?;
+
+        let directory = product_directory.join(&self.directory);
+        let directory = directory
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", directory.display(), e))?;
+        let volume = format!("{}:{}:ro", directory.display(), MOUNT_PATH);
+        let url_variable = format!("{}={}", URL_VARIABLE, url);
+        let image = database.identifier();
+
+        let args = vec![
+            "run", "--rm",
+            "--network", network_name,
+            "-v", &volume,
+            "-e", &url_variable,
+            "--entrypoint", "sh",
+            &image,
+            "-c", MIGRATE_SCRIPT,
+        ];
+        run_command("migrate".white().bold(), docker, args).await
+    }
+
+    /// The migrations as a one-off Compose service that runs once the
+    /// database has started, the way `rushd dev` runs them.
+    pub fn compose_service(&self, directory: &str, network_name: &str, images: &[DockerImage]) -> Result<Mapping, String> {
+        let database = images
+            .iter()
+            .find(|image| image.component_name() == self.database)
+            .ok_or(format!("Migrations target unknown component `{}`", self.database))?;
+        let url = self.dev_url(database, images).ok_or(format!(
+            "No connection URL for the migrations: set {} or POSTGRES_PASSWORD on `{}`",
+            self.connection_variable, self.database
+        ))?;
+
+        let mut service = Mapping::new();
+        compose::set(&mut service, "image", database.identifier());
+        compose::set(&mut service, "entrypoint", "sh");
+        compose::set(&mut service, "command", ["-c", &compose::escape(MIGRATE_SCRIPT)]);
+        compose::set(&mut service, "environment", BTreeMap::from([(URL_VARIABLE, compose::escape(&url))]));
+        compose::set(&mut service, "volumes", [format!("{}:{}:ro", directory, MOUNT_PATH)]);
+        compose::set(&mut service, "depends_on", [&self.database]);
+        compose::set(&mut service, "networks", [network_name]);
+        Ok(service)
+    }
+
+    /// The Job is named after the content of the migrations, so a new
+    /// migration results in a new Job while unchanged ones are left alone.
+    fn job_name(&self, product_directory: &Path) -> Result<String, String> {
+        let mut hash = FNV_OFFSET_BASIS;
+        for file in self.files(product_directory)? {
+            let name = file.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let content = std::fs::read(&file).map_err(|e| e.to_string())?;
+            // Lengths keep name and content boundaries unambiguous
+            for part in [name.as_bytes(), &content] {
+                hash = fnv1a(hash, &(part.len() as u64).to_le_bytes());
+                hash = fnv1a(hash, part);
+            }
+        }
+        Ok(format!("migrations-{:08x}", hash as u32))
+    }
+
+    fn namespace(config: &Config) -> String {
+        format!("{}-{}", config.product_uri(), config.environment())
+    }
+
+    /// Name of the Secret the Job reads the connection URL from
+    pub fn secret_name(config: &Config) -> String {
+        format!("{}-migrations", config.product_uri())
+    }
+
+    /// Values of the Secret the Job reads, when the connection URL is known
+    /// locally.
+    pub fn secret_values(&self, images: &[DockerImage]) -> Option<BTreeMap<String, String>> {
+        let url = self.configured_url(images)?;
+        Some(BTreeMap::from([(self.connection_variable.clone(), url)]))
+    }
+
+    /// Renders the migrations as a Job together with a ConfigMap holding the
+    /// SQL files. The Secret with the connection URL is never rendered, as
+    /// the manifests are committed.
+    pub fn render_manifests(&self, product_directory: &Path, output_directory: &Path, config: &Config, images: &[DockerImage]) -> Result<(), String> {
+        let database = images
+            .iter()
+            .find(|image| image.component_name() == self.database)
+            .ok_or(format!("Migrations target unknown component `{}`", self.database))?;
+
+        let namespace = Self::namespace(config);
+        let name = Self::secret_name(config);
+        let job_name = self.job_name(product_directory)?;
+
+        let mut files = BTreeMap::new();
+        for file in self.files(product_directory)? {
+            let content = std::fs::read_to_string(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+            files.insert(file.file_name().unwrap().to_string_lossy().to_string(), content);
+        }
+
+        let mut manifests = vec![
+            json!({
+                "apiVersion": "v1",
+                "kind": "Namespace",
+                "metadata": { "name": namespace },
+            }),
+            json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": { "name": job_name, "namespace": namespace },
+                "data": files,
+            }),
+        ];
+
+        if self.configured_url(images).is_none() {
+            println!(
+                "{} {} is not known, the Secret `{}` must provide it in {}",
+                "Migrations:".yellow().bold(),
+                self.connection_variable,
+                name,
+                namespace
+            );
+        }
+
+        manifests.push(json!({
+            "apiVersion": "batch/v1",
+            "kind": "Job",
+            "metadata": {
+                "name": job_name,
+                "namespace": namespace,
+                "labels": { "app.kubernetes.io/name": format!("{}-migrations-{}", config.product_uri(), config.environment()) },
+            },
+            "spec": {
+                "backoffLimit": 3,
+                "template": {
+                    "spec": {
+                        "restartPolicy": "Never",
+                        "containers": [{
+                            "name": "migrations",
+                            "image": database.identifier(),
+                            "command": ["sh", "-c", MIGRATE_SCRIPT],
+                            "env": [{
+                                "name": URL_VARIABLE,
+                                "valueFrom": { "secretKeyRef": { "name": name, "key": self.connection_variable } },
+                            }],
+                            "volumeMounts": [{ "name": "migrations", "mountPath": MOUNT_PATH }],
+                        }],
+                        "volumes": [{ "name": "migrations", "configMap": { "name": job_name } }],
+                    },
+                },
+            },
+        }));
+
+        let documents = manifests
+            .iter()
+            .map(|manifest| serde_yaml::to_string(manifest).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let output_directory = output_directory.join(MIGRATIONS_MANIFEST_DIR);
+        std::fs::create_dir_all(&output_directory).map_err(|e| e.to_string())?;
+        std::fs::write(output_directory.join("migrations.yaml"), documents.join("---\n")).map_err(|e| e.to_string())
+    }
+
+    /// Applies the rendered Job and waits for it to complete, so that the
+    /// components are only deployed against a migrated database.
+    pub async fn apply(&self, product_directory: &Path, output_directory: &Path, config: &Config, kubectl: &str) -> Result<(), String> {
+        let manifest = output_directory.join(MIGRATIONS_MANIFEST_DIR).join("migrations.yaml");
+        if !manifest.exists() {
+            return Ok(());
+        }
+
+        let manifest = manifest.display().to_string();
+        run_command("migrate".white().bold(), kubectl, vec!["apply", "-f", &manifest]).await?;
+
+        let job = format!("job/{}", self.job_name(product_directory)?);
+        let namespace = Self::namespace(config);
+        run_command(
+            "migrate".white().bold(),
+            kubectl,
+            vec!["wait", "--for=condition=complete", &job, "-n", &namespace, "--timeout=300s"],
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
@@ -1,7 +1,15 @@
+pub mod compose;
+pub mod compose_import;
 pub mod container_reactor;
+pub mod dependency_graph;
 pub mod docker;
+pub mod log_format;
+pub mod migrations;
+pub mod ports;
 pub mod service_spec;
 pub mod status;
+pub mod volumes;
 
 pub use container_reactor::ContainerReactor;
-pub use service_spec::{ServiceSpec, ServicesSpec};
+pub use log_format::{LogFilter, LogFormat, LogFormatter};
+pub use service_spec::{discovery_env, ServiceSpec, ServicesSpec};
@@ -0,0 +1,143 @@
+use colored::Colorize;
+use std::collections::{BTreeMap, HashSet};
+use std::net::TcpListener;
+use std::path::PathBuf;
+
+pub const FIRST_AUTO_PORT: u16 = 8000;
+
+/// Tests whether a host port can currently be bound.
+pub fn is_port_available(port: u16) -> bool {
+    TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
+
+/// Hands out host ports to components that do not declare one. Chosen ports
+/// are stored per component, so a component keeps its port across runs and
+/// does not change port when the stack is reordered.
+pub struct PortAllocator {
+    path: PathBuf,
+    persisted: BTreeMap<String, u16>,
+    assigned: BTreeMap<String, u16>,
+    reserved: HashSet<u16>,
+    next_port: u16,
+}
+
+impl PortAllocator {
+    /// `reserved` holds the ports declared explicitly in the stack. They are
+    /// never handed out.
+    pub fn load(path: PathBuf, reserved: HashSet<u16>) -> Self {
+        let persisted = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<BTreeMap<String, u16>>(&content).ok())
+            .unwrap_or_default();
+
+        PortAllocator {
+            path,
+            persisted,
+            assigned: BTreeMap::new(),
+            reserved,
+            next_port: FIRST_AUTO_PORT,
+        }
+    }
+
+    fn is_taken(&self, port: u16) -> bool {
+        self.reserved.contains(&port) || self.assigned.values().any(|p| *p == port)
+    }
+
+    /// Returns the port stored for the component, unless it has since been
+    /// declared explicitly by another component or is in use on the host.
+    /// `allocate` then picks a new one, which `save` stores.
+    pub fn persisted_port(&mut self, component_name: &str) -> Option<u16> {
+        let port = *self.persisted.get(component_name)?;
+        if self.is_taken(port) {
+            return None;
+        }
+        if !is_port_available(port) {
+            eprintln!(
+                "{}",
+                format!("Port {} of {} is in use, picking a new one", port, component_name).yellow()
+            );
+            return None;
+        }
+        self.assigned.insert(component_name.to_string(), port);
+        Some(port)
+    }
+
+    /// Picks the next port that is neither declared, assigned nor in use on
+    /// the host. Call after `persisted_port` has been tried for every
+    /// component so that new components do not take over stored ports.
+    pub fn allocate(&mut self, component_name: &str) -> Result<u16, String> {
+        let stored = self.persisted.values().cloned().collect::<HashSet<_>>();
+        loop {
+            let port = self.next_port;
+            if port == u16::MAX {
+                return Err(format!("No free port left for {}", component_name));
+            }
+            self.next_port += 1;
+
+            if self.is_taken(port) || stored.contains(&port) || !is_port_available(port) {
+                continue;
+            }
+
+            self.assigned.insert(component_name.to_string(), port);
+            return Ok(port);
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if self.assigned == self.persisted {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let content = serde_json::to_string_pretty(&self.assigned).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocator(persisted: &[(&str, u16)], reserved: &[u16]) -> PortAllocator {
+        PortAllocator {
+            path: PathBuf::new(),
+            persisted: persisted.iter().map(|(name, port)| (name.to_string(), *port)).collect(),
+            assigned: BTreeMap::new(),
+            reserved: reserved.iter().cloned().collect(),
+            next_port: FIRST_AUTO_PORT,
+        }
+    }
+
+    fn free_port() -> u16 {
+        TcpListener::bind(("0.0.0.0", 0)).unwrap().local_addr().unwrap().port()
+    }
+
+    #[test]
+    fn reuses_free_persisted_port() {
+        let port = free_port();
+        let mut allocator = allocator(&[("backend", port)], &[]);
+        assert_eq!(allocator.persisted_port("backend"), Some(port));
+    }
+
+    #[test]
+    fn skips_persisted_port_declared_by_another_component() {
+        let port = free_port();
+        let mut allocator = allocator(&[("backend", port)], &[port]);
+        assert_eq!(allocator.persisted_port("backend"), None);
+    }
+
+    #[test]
+    fn replaces_persisted_port_in_use() {
+        let listener = TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut allocator = allocator(&[("backend", port)], &[]);
+        assert_eq!(allocator.persisted_port("backend"), None);
+
+        let new_port = allocator.allocate("backend").unwrap();
+        assert_ne!(new_port, port);
+        assert_eq!(allocator.assigned.get("backend"), Some(&new_port));
+    }
+}
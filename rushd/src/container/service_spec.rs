@@ -1,12 +1,47 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceSpec {
     pub name: String,
+    /// Host name other containers use to reach the service
+    pub host: String,
     pub port: u16,
     pub target_port: u16,
     pub mount_point: Option<String>,
+    pub subdomain: Option<String>,
+    /// Port of the component's `<component>-service` in Kubernetes, set for
+    /// components with manifests
+    pub cluster_port: Option<u16>,
 }
 
 pub type ServicesSpec = HashMap<String, ServiceSpec>;
+
+impl ServiceSpec {
+    /// Prefix of the discovery variables of the service, `API_GATEWAY` for
+    /// `api-gateway`.
+    pub fn env_prefix(&self) -> String {
+        self.name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect()
+    }
+}
+
+/// Variables through which components find each other: `<COMPONENT>_HOST`
+/// and `<COMPONENT>_PORT` for every service, at the address `address` gives
+/// for it. Services the ingress routes, which have a mount point or a
+/// subdomain, speak HTTP and also get `<COMPONENT>_URL`.
+pub fn discovery_env<F: Fn(&ServiceSpec) -> (String, u16)>(services: &ServicesSpec, address: F) -> BTreeMap<String, String> {
+    let mut env = BTreeMap::new();
+    for service in services.values() {
+        let (host, port) = address(service);
+        let prefix = service.env_prefix();
+        if service.mount_point.is_some() || service.subdomain.is_some() {
+            env.insert(format!("{}_URL", prefix), format!("http://{}:{}", host, port));
+        }
+        env.insert(format!("{}_HOST", prefix), host);
+        env.insert(format!("{}_PORT", prefix), port.to_string());
+    }
+    env
+}
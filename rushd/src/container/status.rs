@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Status {
     Awaiting,
     InProgress,
@@ -6,3 +9,16 @@ pub enum Status {
     Finished(i32),
     Terminate,
 }
+
+impl Status {
+    pub fn describe(&self) -> String {
+        match self {
+            Status::Awaiting => "starting".to_string(),
+            Status::InProgress => "running".to_string(),
+            Status::StartupCompleted => "ready".to_string(),
+            Status::Reinitializing => "restarting".to_string(),
+            Status::Finished(code) => format!("exited ({})", code),
+            Status::Terminate => "stopped".to_string(),
+        }
+    }
+}
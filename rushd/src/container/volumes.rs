@@ -0,0 +1,253 @@
+use crate::container::docker::DockerImage;
+use crate::utils::run_command;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+/// Small image used to read and write volume contents, so that files owned
+/// by the container's user can be handled without root on the host.
+const HELPER_IMAGE: &str = "alpine:3";
+
+#[derive(Debug, Clone)]
+pub enum VolumeSource {
+    /// Docker volume, named `<product_uri>-<name>`
+    Named(String),
+    /// Host directory mounted into the container
+    Bind(PathBuf),
+}
+
+/// A volume of a component, as mounted by `rushd dev`.
+#[derive(Debug, Clone)]
+pub struct Mount {
+    pub component: String,
+    pub source: VolumeSource,
+    pub container_path: String,
+}
+
+impl Mount {
+    /// The mounts of all components, or of a single one.
+    pub fn from_images(images: &[DockerImage], component: Option<&str>) -> Result<Vec<Mount>, String> {
+        if let Some(component) = component {
+            if !images.iter().any(|image| image.component_name() == component) {
+                return Err(format!("Unknown component `{}`", component));
+            }
+        }
+
+        let mut mounts = Vec::new();
+        for image in images {
+            let spec = image.spec();
+            if component.is_some_and(|component| component != spec.component_name) {
+                continue;
+            }
+
+            let mut volumes = spec.volumes.unwrap_or_default().into_iter().collect::<Vec<_>>();
+            volumes.sort();
+            for (source, container_path) in volumes {
+                let source = if source.starts_with('/') {
+                    VolumeSource::Bind(PathBuf::from(source))
+                } else {
+                    VolumeSource::Named(source)
+                };
+                mounts.push(Mount {
+                    component: spec.component_name.clone(),
+                    source,
+                    container_path,
+                });
+            }
+        }
+        Ok(mounts)
+    }
+
+    fn docker_source(&self) -> String {
+        match &self.source {
+            VolumeSource::Named(name) => name.clone(),
+            VolumeSource::Bind(path) => path.display().to_string(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {} ({})", self.component, self.container_path, self.docker_source())
+    }
+
+    /// Archives are named after the path inside the container, which stays
+    /// the same when the source of a volume is changed.
+    fn archive_name(&self) -> String {
+        format!("{}.tar.gz", slug::slugify(&self.container_path))
+    }
+
+    async fn run_helper(&self, docker: &str, backup_directory: Option<&Path>, script: &str) -> Result<(), String> {
+        let volume = format!("{}:/volume", self.docker_source());
+        let mut args = vec!["run".to_string(), "--rm".to_string(), "-v".to_string(), volume];
+        if let Some(backup_directory) = backup_directory {
+            args.push("-v".to_string());
+            args.push(format!("{}:/backup", backup_directory.display()));
+        }
+        args.extend([HELPER_IMAGE.to_string(), "sh".to_string(), "-c".to_string(), script.to_string()]);
+
+        run_command("volumes".white().bold(), docker, args.iter().map(|arg| arg.as_str()).collect()).await
+    }
+}
+
+/// Whether git tracks files under `path`. When git cannot tell, the path is
+/// treated as tracked so that it is kept.
+fn is_tracked_by_git(path: &Path) -> bool {
+    let output = std::process::Command::new("git")
+        .args(["ls-files", "--"])
+        .arg(path)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => !output.stdout.is_empty(),
+        Ok(output) => {
+            eprintln!("Could not check {} with git: {}", path.display(), String::from_utf8_lossy(&output.stderr).trim());
+            true
+        }
+        Err(e) => {
+            eprintln!("Could not check {} with git: {}", path.display(), e);
+            true
+        }
+    }
+}
+
+/// Empties the volumes. Docker volumes are removed. Host directories are
+/// only cleared when they are inside the product and contain no files
+/// tracked by git, so source code mounted into a container is never touched.
+pub async fn reset(docker: &str, product_directory: &Path, mounts: &[Mount]) -> Result<(), String> {
+    for mount in mounts {
+        match &mount.source {
+            VolumeSource::Named(name) => {
+                println!("Removing volume {}", mount.describe());
+                run_command("volumes".white().bold(), docker, vec!["volume", "rm", "-f", name]).await?;
+            }
+            VolumeSource::Bind(path) => {
+                if !path.exists() {
+                    continue;
+                }
+                if !path.starts_with(product_directory) || is_tracked_by_git(path) {
+                    println!("Keeping {}, it is outside the product or tracked by git", mount.describe());
+                    continue;
+                }
+                println!("Clearing {}", mount.describe());
+                mount.run_helper(docker, None, "find /volume -mindepth 1 -delete").await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Named sets of volume archives under `target/rushd/snapshots`, laid out as
+/// `<name>/<component>/<container path>.tar.gz`.
+pub struct Snapshots {
+    directory: PathBuf,
+    docker: String,
+}
+
+impl Snapshots {
+    pub fn new(product_directory: &Path, docker: &str) -> Self {
+        Snapshots {
+            directory: product_directory.join("target").join("rushd").join("snapshots"),
+            docker: docker.to_string(),
+        }
+    }
+
+    /// Snapshot names become directory names, so they are limited to
+    /// lowercase letters, digits and dashes.
+    fn validate_name(name: &str) -> Result<(), String> {
+        if name.is_empty() || slug::slugify(name) != name {
+            return Err(format!("Invalid snapshot name `{}`, use lowercase letters, digits and dashes", name));
+        }
+        Ok(())
+    }
+
+    fn component_directory(&self, name: &str, component: &str) -> PathBuf {
+        self.directory.join(name).join(component)
+    }
+
+    pub async fn snapshot(&self, name: &str, mounts: &[Mount]) -> Result<(), String> {
+        Self::validate_name(name)?;
+        if mounts.is_empty() {
+            return Err("No volumes to snapshot".to_string());
+        }
+
+        // Taking a snapshot again replaces it for the components included
+        let mut components = mounts.iter().map(|mount| mount.component.clone()).collect::<Vec<_>>();
+        components.dedup();
+        for component in &components {
+            let directory = self.component_directory(name, component);
+            if directory.exists() {
+                std::fs::remove_dir_all(&directory).map_err(|e| e.to_string())?;
+            }
+        }
+
+        for mount in mounts {
+            if let VolumeSource::Bind(path) = &mount.source {
+                if !path.exists() {
+                    println!("Skipping {}, it does not exist", mount.describe());
+                    continue;
+                }
+            }
+
+            let directory = self.component_directory(name, &mount.component);
+            std::fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+            let directory = directory.canonicalize().map_err(|e| e.to_string())?;
+
+            println!("Saving {}", mount.describe());
+            let script = format!("tar czf /backup/{} -C /volume .", mount.archive_name());
+            mount.run_helper(&self.docker, Some(&directory), &script).await?;
+        }
+
+        println!("{}", format!("Snapshot {} saved in {}", name, self.directory.join(name).display()).white().bold());
+        Ok(())
+    }
+
+    pub async fn restore(&self, name: &str, mounts: &[Mount]) -> Result<(), String> {
+        Self::validate_name(name)?;
+        if !self.directory.join(name).exists() {
+            return Err(format!("Snapshot {} not found", name));
+        }
+
+        for mount in mounts {
+            let directory = self.component_directory(name, &mount.component);
+            if !directory.join(mount.archive_name()).exists() {
+                println!("Skipping {}, it is not in the snapshot", mount.describe());
+                continue;
+            }
+            let directory = directory.canonicalize().map_err(|e| e.to_string())?;
+
+            if let VolumeSource::Bind(path) = &mount.source {
+                std::fs::create_dir_all(path).map_err(|e| e.to_string())?;
+            }
+
+            println!("Restoring {}", mount.describe());
+            let script = format!("find /volume -mindepth 1 -delete && tar xzf /backup/{} -C /volume", mount.archive_name());
+            mount.run_helper(&self.docker, Some(&directory), &script).await?;
+        }
+
+        println!("{}", format!("Snapshot {} restored", name).white().bold());
+        Ok(())
+    }
+
+    /// Snapshot names with the components each of them holds.
+    pub fn list(&self) -> Vec<(String, Vec<String>)> {
+        let subdirectories = |directory: &Path| -> Vec<String> {
+            let mut names = std::fs::read_dir(directory)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .filter(|entry| entry.path().is_dir())
+                        .map(|entry| entry.file_name().to_string_lossy().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            names.sort();
+            names
+        };
+
+        subdirectories(&self.directory)
+            .into_iter()
+            .filter(|name| Self::validate_name(name).is_ok())
+            .map(|name| {
+                let components = subdirectories(&self.directory.join(&name));
+                (name, components)
+            })
+            .collect()
+    }
+}
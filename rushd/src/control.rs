@@ -0,0 +1,199 @@
+use crate::container::status::Status;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+/// A request to a running `rushd dev`. Sent as one JSON object per line,
+/// for instance `{"command": "restart", "component": "backend"}`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ControlRequest {
+    Status,
+    Restart { component: String },
+    Rebuild { component: String },
+    Stop { component: String },
+    /// Streams log lines until the client disconnects
+    Logs { component: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComponentStatus {
+    pub component: String,
+    /// `None` until the component has been started
+    pub status: Option<Status>,
+    pub port: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ControlResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub components: Option<Vec<ComponentStatus>>,
+}
+
+impl ControlResponse {
+    pub fn ok(message: &str) -> Self {
+        ControlResponse {
+            ok: true,
+            message: Some(message.to_string()),
+            components: None,
+        }
+    }
+
+    pub fn error(message: &str) -> Self {
+        ControlResponse {
+            ok: false,
+            message: Some(message.to_string()),
+            components: None,
+        }
+    }
+}
+
+/// A line of output of a component, as sent to `logs` subscribers.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogLine {
+    pub component: String,
+    pub line: String,
+}
+
+/// A request together with the channel the reactor answers on.
+pub type ControlMessage = (ControlRequest, oneshot::Sender<ControlResponse>);
+
+/// Serves the control API of a dev session on a unix socket. Requests are
+/// handed to the reactor, which owns the running components. Log
+/// subscriptions are served from the log broadcast directly.
+pub struct ControlServer {
+    path: PathBuf,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl ControlServer {
+    pub async fn start(
+        path: PathBuf,
+        requests: mpsc::UnboundedSender<ControlMessage>,
+        logs: broadcast::Sender<LogLine>,
+    ) -> Result<Self, String> {
+        if path.exists() {
+            if UnixStream::connect(&path).await.is_ok() {
+                return Err(format!("A dev session is already running ({})", path.display()));
+            }
+            // Left behind by a session that did not shut down cleanly
+            std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let listener = UnixListener::bind(&path)
+            .map_err(|e| format!("Failed to bind control socket {}: {}", path.display(), e))?;
+
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let requests = requests.clone();
+                let logs = logs.clone();
+                tokio::spawn(async move {
+                    let _ = Self::handle(stream, requests, logs).await;
+                });
+            }
+        });
+
+        Ok(ControlServer { path, handle })
+    }
+
+    async fn handle(
+        stream: UnixStream,
+        requests: mpsc::UnboundedSender<ControlMessage>,
+        logs: broadcast::Sender<LogLine>,
+    ) -> Result<(), String> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
+            let request = match serde_json::from_str::<ControlRequest>(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    write_json(&mut writer, &ControlResponse::error(&format!("Invalid request: {}", e))).await?;
+                    continue;
+                }
+            };
+
+            if let ControlRequest::Logs { component } = request {
+                let mut receiver = logs.subscribe();
+                loop {
+                    match receiver.recv().await {
+                        Ok(log_line) => {
+                            if component.as_ref().is_some_and(|component| *component != log_line.component) {
+                                continue;
+                            }
+                            write_json(&mut writer, &log_line).await?;
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                    }
+                }
+            }
+
+            let (reply_sender, reply_receiver) = oneshot::channel();
+            requests
+                .send((request, reply_sender))
+                .map_err(|_| "The dev session has stopped".to_string())?;
+            let response = reply_receiver
+                .await
+                .unwrap_or(ControlResponse::error("The dev session has stopped"));
+            write_json(&mut writer, &response).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn write_json<W: AsyncWriteExt + Unpin, T: Serialize>(writer: &mut W, value: &T) -> Result<(), String> {
+    let mut json = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await.map_err(|e| e.to_string())
+}
+
+async fn connect(path: &Path) -> Result<UnixStream, String> {
+    UnixStream::connect(path)
+        .await
+        .map_err(|_| format!("No dev session is running ({} is not available)", path.display()))
+}
+
+/// Sends a single request to a running dev session.
+pub async fn send(path: &Path, request: &ControlRequest) -> Result<ControlResponse, String> {
+    let stream = connect(path).await?;
+    let (reader, mut writer) = stream.into_split();
+    write_json(&mut writer, request).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("The dev session closed the connection".to_string())?;
+    serde_json::from_str(&line).map_err(|e| format!("Invalid response: {}", e))
+}
+
+/// Prints the output of the components until the session ends.
+pub async fn follow_logs(path: &Path, component: Option<String>) -> Result<(), String> {
+    let stream = connect(path).await?;
+    let (reader, mut writer) = stream.into_split();
+    write_json(&mut writer, &ControlRequest::Logs { component }).await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
+        if let Ok(log_line) = serde_json::from_str::<LogLine>(&line) {
+            println!("{} |   {}", log_line.component.bold(), log_line.line);
+        }
+    }
+    Ok(())
+}
@@ -1,71 +1,294 @@
-use glob::Pattern as GlobPattern;
+use glob::{MatchOptions, Pattern as GlobPattern};
+use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", ".rushdignore"];
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Ignore rules collected from `.git/info/exclude` and every `.gitignore` and
+/// `.rushdignore` between the repository root and the watched directory,
+/// including the ones nested below it. Files are kept in precedence order so
+/// that, as in git, the last matching pattern decides.
 pub struct GitIgnore {
-    ignore_patterns: Vec<Pattern>,
+    ignore_files: Vec<IgnoreFile>,
+}
+
+struct IgnoreFile {
+    base: PathBuf,
+    patterns: Vec<Pattern>,
 }
 
 pub struct Pattern {
     pattern: GlobPattern,
-    original_pattern: String,
+    negated: bool,
+    directory_only: bool,
+    anchored: bool,
 }
 
 impl Pattern {
-    pub fn new(pattern: String) -> Self {
-        // TODO: Implement a more complete version of gitignore.
-        let glob_pattern = match pattern.starts_with("/") {
-            true => GlobPattern::new(&pattern).expect("Failed to compile glob pattern"),
-            false => GlobPattern::new(&format!("**/{}", pattern))
-                .expect("Failed to compile glob pattern"),
+    /// Parses a single line of an ignore file. Returns `None` for blank lines,
+    /// comments and patterns that cannot be compiled.
+    pub fn new(line: &str) -> Option<Self> {
+        let mut pattern = trim_unescaped_trailing_spaces(line);
+
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+
+        let negated = pattern.starts_with('!');
+        if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern = pattern[1..].to_string();
+        }
+
+        let directory_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+
+        // A slash at the beginning or in the middle anchors the pattern to the
+        // directory of the ignore file, otherwise it matches at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+
+        let pattern = match GlobPattern::new(pattern) {
+            Ok(p) => p,
+            // Git treats a `**` that is not a whole path component like `*`
+            Err(_) => GlobPattern::new(&pattern.replace("**", "*")).ok()?,
         };
-        Pattern {
-            pattern: glob_pattern,
-            original_pattern: pattern,
+
+        Some(Pattern {
+            pattern,
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+
+    /// Whether the pattern starts with `!` and re-includes what it matches
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Tests a path given relative to the directory of the ignore file.
+    pub fn matches(&self, relative_path: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+
+        if self.anchored {
+            self.pattern.matches_with(relative_path, MATCH_OPTIONS)
+        } else {
+            let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+            self.pattern.matches_with(name, MATCH_OPTIONS)
         }
     }
+}
 
-    pub fn matches(&self, path: &Path) -> bool {
-        let path_str = path
-            .to_str()
-            .expect("Path could not be converted to string");
-        path_str.contains(&self.original_pattern) || self.pattern.matches(path_str)
+fn trim_unescaped_trailing_spaces(line: &str) -> String {
+    let trimmed = line.trim_end();
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        // `foo\ ` keeps the escaped space
+        format!("{} ", &trimmed[..trimmed.len() - 1])
+    } else {
+        trimmed.to_string()
+    }
+}
+
+impl IgnoreFile {
+    fn load(base: &Path, path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let patterns = content.lines().filter_map(Pattern::new).collect::<Vec<_>>();
+        Some(IgnoreFile {
+            base: base.to_path_buf(),
+            patterns,
+        })
+    }
+
+    /// Returns `Some(true)` if the path is ignored, `Some(false)` if it is
+    /// explicitly re-included and `None` if no pattern applies.
+    fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative_path = path.strip_prefix(&self.base).ok()?;
+        let relative_path = relative_path.to_str()?.replace('\\', "/");
+        if relative_path.is_empty() {
+            return None;
+        }
+
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(&relative_path, is_dir))
+            .map(|pattern| !pattern.negated)
     }
 }
 
 impl GitIgnore {
     pub fn new(start_path: &Path) -> Self {
-        let mut current_path = start_path.to_path_buf();
-        let mut gitignore_path: PathBuf;
-
-        // Walk up the directory tree to find the .gitignore file
-        loop {
-            gitignore_path = current_path.join(".gitignore");
-            if gitignore_path.exists() {
-                break;
+        let start_path = start_path
+            .canonicalize()
+            .unwrap_or_else(|_| start_path.to_path_buf());
+
+        let repository_root = start_path
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(|dir| dir.to_path_buf());
+
+        let mut ret = GitIgnore {
+            ignore_files: Vec::new(),
+        };
+
+        if let Some(root) = &repository_root {
+            let exclude_path = root.join(".git").join("info").join("exclude");
+            if let Some(file) = IgnoreFile::load(root, &exclude_path) {
+                ret.ignore_files.push(file);
             }
-            if !current_path.pop() {
-                panic!("No .gitignore file found in any parent directory");
+        }
+
+        // Ignore files from the repository root down to the watched directory
+        let mut parents = start_path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| match &repository_root {
+                Some(root) => dir.starts_with(root),
+                None => false,
+            })
+            .collect::<Vec<_>>();
+        parents.reverse();
+        for dir in parents {
+            ret.load_directory(dir);
+        }
+
+        ret.load_tree(&start_path);
+        ret
+    }
+
+    fn load_directory(&mut self, dir: &Path) {
+        for name in IGNORE_FILE_NAMES {
+            if let Some(file) = IgnoreFile::load(dir, &dir.join(name)) {
+                self.ignore_files.push(file);
             }
         }
+    }
 
-        // Read the .gitignore file
-        let gitignore_content =
-            fs::read_to_string(gitignore_path).expect("Failed to read .gitignore file");
+    fn load_tree(&mut self, dir: &Path) {
+        self.load_directory(dir);
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
 
-        // Split the content into lines, create Pattern instances, and collect them into a Vec<Pattern>
-        let ignore_patterns = gitignore_content
-            .lines()
-            .map(|line| line.trim().to_string())
-            .filter(|line| !line.is_empty() && !line.starts_with("#")) // Filter out empty lines and comments
-            .map(|pattern| Pattern::new(pattern))
+        let mut subdirectories = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name() != Some(OsStr::new(".git")))
             .collect::<Vec<_>>();
-        GitIgnore { ignore_patterns }
+        subdirectories.sort();
+
+        for subdirectory in subdirectories {
+            // Ignored directories cannot re-include anything, so there is no
+            // need to look for ignore files inside them.
+            if !self.is_ignored(&subdirectory, true) {
+                self.load_tree(&subdirectory);
+            }
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for file in &self.ignore_files {
+            if let Some(decision) = file.decide(path, is_dir) {
+                ignored = decision;
+            }
+        }
+        ignored
     }
 
     pub fn ignores(&self, path: &Path) -> bool {
-        self.ignore_patterns
-            .iter()
-            .any(|pattern| pattern.matches(path))
+        if path.components().any(|c| c.as_os_str() == ".git") {
+            return true;
+        }
+
+        let absolute_path;
+        let path = if path.is_relative() {
+            absolute_path = std::env::current_dir()
+                .map(|cwd| cwd.join(path))
+                .unwrap_or_else(|_| path.to_path_buf());
+            absolute_path.as_path()
+        } else {
+            path
+        };
+
+        // A file inside an ignored directory is ignored regardless of any
+        // negated pattern matching the file itself.
+        let ancestors = path.ancestors().skip(1).collect::<Vec<_>>();
+        for ancestor in ancestors.into_iter().rev() {
+            if self.is_ignored(ancestor, true) {
+                return true;
+            }
+        }
+
+        self.is_ignored(path, path.is_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    fn matches(pattern: &str, path: &str, is_dir: bool) -> bool {
+        Pattern::new(pattern).unwrap().matches(path, is_dir)
+    }
+
+    #[test]
+    fn skips_blank_lines_and_comments() {
+        assert!(Pattern::new("").is_none());
+        assert!(Pattern::new("   ").is_none());
+        assert!(Pattern::new("# comment").is_none());
+        assert!(Pattern::new("\\#file").is_some());
+    }
+
+    #[test]
+    fn negation() {
+        let pattern = Pattern::new("!keep.log").unwrap();
+        assert!(pattern.is_negated());
+        assert!(pattern.matches("keep.log", false));
+        assert!(!Pattern::new("\\!keep.log").unwrap().is_negated());
+    }
+
+    #[test]
+    fn directory_only() {
+        assert!(matches("build/", "build", true));
+        assert!(!matches("build/", "build", false));
+        assert!(matches("build", "build", false));
+    }
+
+    #[test]
+    fn unanchored_matches_at_any_depth() {
+        assert!(matches("*.log", "debug.log", false));
+        assert!(matches("*.log", "a/b/debug.log", false));
+        assert!(!matches("*.log", "debug.txt", false));
+    }
+
+    #[test]
+    fn anchored_matches_relative_to_ignore_file() {
+        assert!(matches("/target", "target", true));
+        assert!(!matches("/target", "sub/target", true));
+        assert!(matches("doc/*.md", "doc/a.md", false));
+        assert!(!matches("doc/*.md", "doc/x/a.md", false));
+        assert!(!matches("doc/*.md", "sub/doc/a.md", false));
+    }
+
+    #[test]
+    fn double_star_matches_across_directories() {
+        assert!(matches("a/**/b", "a/b", false));
+        assert!(matches("a/**/b", "a/x/y/b", false));
     }
 }
@@ -4,15 +4,21 @@ mod toolchain;
 mod cluster;
 mod builder;
 mod gitignore;
+mod watcher;
+mod certs;
+mod proxy;
+mod control;
 
 use crate::toolchain::Platform;
-use clap::{arg, Command,Arg};
+use clap::{arg, Command, Arg, ArgAction};
 use tokio::io;
 use std::{path::Path, sync::Arc};
-use crate::container::ContainerReactor;
+use crate::container::{ContainerReactor, LogFilter};
+use crate::container::compose_import;
+use crate::control::ControlRequest;
 use crate::utils::Directory;
 use crate::toolchain::ToolchainContext;
-use cluster::Minikube;
+use cluster::local_cluster;
 use colored::Colorize;
 use crate::builder::Config;
 use std::env;
@@ -69,9 +75,15 @@ fn setup_environment() {
 #[derive(Debug, Deserialize)]
 struct RushdConfig {
     env: HashMap<String, String>,
+    /// `minikube` or `kind`, used by the `cluster` commands
+    local_cluster: Option<String>,
+    /// Serve the local cluster under `<product>.<ip>.nip.io` rather than
+    /// `<product>.local`, which needs an entry in /etc/hosts
+    #[serde(default)]
+    local_cluster_nip_io: bool,
 }
 
-fn load_config() {
+fn load_config() -> RushdConfig {
     let config_path = "rushd.yaml";
     let mut file = File::open(config_path).expect("Unable to open the config file");
     let mut contents = String::new();
@@ -79,23 +91,81 @@ fn load_config() {
 
     let config: RushdConfig = serde_yaml::from_str(&contents).expect("Error parsing the config file");
 
-    for (key, value) in config.env {
+    for (key, value) in &config.env {
         std::env::set_var(key, value);
     }
+    config
 }
 
 
 
 
 
+fn import(root_dir: &str) {
+    let matches = Command::new("rushd import")
+        .about("Creates a product from another tool's configuration")
+        .subcommand_required(true)
+        .subcommand(Command::new("compose")
+            .about("Translates a docker-compose file into the stack.yaml of a new product")
+            .arg(Arg::new("file").required(true))
+            .arg(arg!(product : --product <PRODUCT> "Name of the product to create").required(true))
+        )
+        .bin_name("rushd import")
+        .get_matches_from(std::env::args().skip(1));
+
+    if let Some(matches) = matches.subcommand_matches("compose") {
+        let file = matches.get_one::<String>("file").unwrap();
+        let product = matches.get_one::<String>("product").unwrap();
+
+        let product_directory = Path::new(root_dir).join("products").join(product);
+        let stack_path = product_directory.join("stack.yaml");
+        if stack_path.exists() {
+            eprintln!("{} already exists", stack_path.display());
+            std::process::exit(1);
+        }
+
+        let file = env::current_dir().expect("Failed to get current directory").join(file);
+        let import = match compose_import::import(&file, &product_directory) {
+            Ok(import) => import,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+        if let Err(e) = std::fs::create_dir_all(&product_directory).and_then(|_| std::fs::write(&stack_path, import.stack)) {
+            eprintln!("Failed to write {}: {}", stack_path.display(), e);
+            std::process::exit(1);
+        }
+        println!("Wrote {}", stack_path.display());
+
+        if !import.untranslated.is_empty() {
+            println!("{}", "Not translated:".yellow().bold());
+            for untranslated in import.untranslated {
+                println!("    {}", untranslated);
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> io::Result<()> {
     setup_environment();
 
     // TODO: Get the rushd root by go levels up until you find ".git" directory
     let root_dir = std::env::var("RUSHD_ROOT").unwrap();
+
+    // Importing creates the product, so it is handled before the product
+    // name is required
+    if std::env::args().nth(1).as_deref() == Some("import") {
+        import(&root_dir);
+        return Ok(());
+    }
+
+    // Paths given on the command line are relative to where rushd was started
+    let invocation_dir = env::current_dir().expect("Failed to get current directory");
     let _guard = Directory::chdir(&root_dir);
-    load_config();
+    let rushd_config = load_config();
 
     dotenv::dotenv().ok();    
     
@@ -135,12 +205,90 @@ async fn main() -> io::Result<()> {
                 .about("Describes the current k8s")
             )                        
         )
-        .subcommand(Command::new("dev"))
+        .subcommand(Command::new("dev")
+            .arg(arg!(log_level : --"log-level" <LEVEL> "Minimum level of structured (JSON) log lines to show"))
+            .arg(arg!(log_filter : --"log-filter" <FILTER> "Only show structured log lines where KEY=VALUE")
+                .action(ArgAction::Append))
+            .arg(arg!(debounce : --debounce <MS> "Milliseconds to wait for further file changes before rebuilding")
+                .value_parser(clap::value_parser!(u64)))
+            .arg(arg!(fresh : --fresh "Start from empty volumes"))
+        )
+        .subcommand(Command::new("volumes")
+            .about("Manages the volumes of the dev containers")
+            .subcommand_required(true)
+            .subcommand(Command::new("snapshot")
+                .about("Saves the volumes under a name")
+                .arg(Arg::new("name").required(true))
+                .arg(Arg::new("component_name"))
+            )
+            .subcommand(Command::new("restore")
+                .about("Replaces the volumes with a snapshot")
+                .arg(Arg::new("name").required(true))
+                .arg(Arg::new("component_name"))
+            )
+            .subcommand(Command::new("list")
+                .about("Lists the snapshots")
+                .arg(Arg::new("name"))
+            )
+        )
+        .subcommand(Command::new("run")
+            .about("Builds and runs a single component")
+            .arg(Arg::new("component_name").required(true))
+        )
+        .subcommand(Command::new("exec")
+            .about("Runs a command in the dev container of a component")
+            .arg(Arg::new("component_name").required(true))
+            .arg(Arg::new("command")
+                .required(true)
+                .num_args(1..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true))
+        )
+        .subcommand(Command::new("shell")
+            .about("Opens a shell in the dev container of a component")
+            .arg(Arg::new("component_name").required(true))
+        )
+        .subcommand(Command::new("export")
+            .about("Exports the stack for other tools")
+            .subcommand_required(true)
+            .subcommand(Command::new("compose")
+                .about("Writes the dev stack as a Docker Compose file")
+                .arg(arg!(output : -o --output <FILE> "File to write, docker-compose.yaml in the product directory by default"))
+            )
+        )
+        .subcommand(Command::new("ctl")
+            .about("Controls a running dev session")
+            .subcommand_required(true)
+            .subcommand(Command::new("status")
+                .about("Shows the status of the components")
+            )
+            .subcommand(Command::new("restart")
+                .about("Restarts a component without rebuilding it")
+                .arg(Arg::new("component_name").required(true))
+            )
+            .subcommand(Command::new("rebuild")
+                .about("Rebuilds and restarts a component")
+                .arg(Arg::new("component_name").required(true))
+            )
+            .subcommand(Command::new("stop")
+                .about("Stops a component")
+                .arg(Arg::new("component_name").required(true))
+            )
+            .subcommand(Command::new("logs")
+                .about("Follows the output of the components")
+                .arg(Arg::new("component_name"))
+            )
+        )
         .subcommand(Command::new("build"))
         .subcommand(Command::new("push"))
-        .subcommand(Command::new("minikube")
-            .about("Runs tasks on minikube")
-            .subcommand(Command::new("dev"))
+        .subcommand(Command::new("cluster")
+            .about("Runs tasks on the local cluster set with local_cluster in rushd.yaml")
+            .alias("minikube")
+            .subcommand(Command::new("dev")
+                .about("Deploys to the local cluster and redeploys components when their files change")
+                .arg(arg!(debounce : --debounce <MS> "Milliseconds to wait for further file changes before rebuilding")
+                    .value_parser(clap::value_parser!(u64)))
+            )
             .subcommand(Command::new("start"))
             .subcommand(Command::new("stop"))
             .subcommand(Command::new("delete"))
@@ -157,8 +305,15 @@ async fn main() -> io::Result<()> {
         .get_matches();
 
 
+    let cluster_dev = matches
+        .subcommand_matches("cluster")
+        .is_some_and(|matches| matches.subcommand_matches("dev").is_some());
+
     let target_arch = if let Some(target_arch) = matches.get_one::<String>("target_arch") {
         target_arch.clone()
+    } else if cluster_dev {
+        // Local clusters run images of the host architecture
+        std::env::consts::ARCH.to_string()
     } else {
         "x86_64".to_string()
     };
@@ -198,6 +353,43 @@ async fn main() -> io::Result<()> {
     let toolchain = Arc::new(ToolchainContext::new(Platform::default(), Platform::new(&target_os, &target_arch)));
     toolchain.setup_env();
 
+    let cluster = match local_cluster(rushd_config.local_cluster.as_deref(), toolchain.clone()) {
+        Ok(cluster) => cluster,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // On a local cluster the product is served under a local domain that
+    // resolves to the ingress address
+    let config = if cluster_dev {
+        let ip = match cluster.ingress_address().await {
+            Ok(ip) => ip,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        if rushd_config.local_cluster_nip_io {
+            config.with_domain(&format!("{}.{}.nip.io", config.product_uri(), ip))
+        } else {
+            let domain = format!("{}.local", config.product_uri());
+            let hosts = std::fs::read_to_string("/etc/hosts").unwrap_or_default();
+            let resolved = hosts.lines().any(|line| {
+                let mut fields = line.split('#').next().unwrap_or_default().split_whitespace();
+                fields.next() == Some(ip.as_str()) && fields.any(|name| name == domain)
+            });
+            if !resolved {
+                println!("{}", format!("Add this line to /etc/hosts to reach {}:", domain).yellow().bold());
+                println!("    {} {}", ip, domain);
+            }
+            config.with_domain(&domain)
+        }
+    } else {
+        config
+    };
+
     
     let mut reactor = match ContainerReactor::from_product_dir(config.clone(), toolchain.clone()) {
         Ok(reactor) => reactor,
@@ -207,7 +399,6 @@ async fn main() -> io::Result<()> {
         }
     };   
 
-    let minikube = Minikube::new(toolchain.clone());     
 
     if let Some(matches) = matches.subcommand_matches("describe") {
         if let Some(_) = matches.subcommand_matches("toolchain") {
@@ -275,9 +466,33 @@ async fn main() -> io::Result<()> {
     }
 
 
-    if let Some(matches) = matches.subcommand_matches("minikube") {
+    if let Some(matches) = matches.subcommand_matches("cluster") {
+        if let Some(matches) = matches.subcommand_matches("dev") {
+            if let Some(debounce) = matches.get_one::<u64>("debounce") {
+                reactor.set_debounce(std::time::Duration::from_millis(*debounce));
+            }
+
+            if !toolchain.has_kubectl() {
+                eprintln!("kubectl not found");
+                std::process::exit(1);
+            }
+
+            let result = match reactor.select_kubernetes_context(&cluster.context()).await {
+                Ok(_) => reactor.cluster_dev(cluster.as_ref()).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(_) => {
+                    return Ok(());
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         if let Some(_) = matches.subcommand_matches("start") {
-            match minikube.start().await {
+            match cluster.start().await {
                 Ok(_) => {
                     return Ok(());
                 },
@@ -288,7 +503,7 @@ async fn main() -> io::Result<()> {
             }
         }
         if let Some(_) = matches.subcommand_matches("stop") {
-            match minikube.stop().await {
+            match cluster.stop().await {
                 Ok(_) => {
                     return Ok(());
                 },
@@ -299,7 +514,7 @@ async fn main() -> io::Result<()> {
             }
         }        
         if let Some(_) = matches.subcommand_matches("delete") {
-            match minikube.delete().await {
+            match cluster.delete().await {
                 Ok(_) => {
                     return Ok(());
                 },
@@ -311,7 +526,31 @@ async fn main() -> io::Result<()> {
         }               
     }
 
-    if let Some(_) = matches.subcommand_matches("dev") {        
+    if let Some(matches) = matches.subcommand_matches("dev") {
+        let log_level = matches.get_one::<String>("log_level").map(|s| s.as_str());
+        let log_filters = matches.get_many::<String>("log_filter")
+            .map(|filters| filters.map(|s| s.as_str()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        match LogFilter::new(log_level, log_filters) {
+            Ok(log_filter) => reactor.set_log_filter(log_filter),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+
+        if let Some(debounce) = matches.get_one::<u64>("debounce") {
+            reactor.set_debounce(std::time::Duration::from_millis(*debounce));
+        }
+
+        if matches.get_flag("fresh") {
+            reactor.clean().await;
+            if let Err(e) = reactor.reset_volumes().await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+
         match reactor.launch().await {
             Ok(_) => {
                 return Ok(());
@@ -323,6 +562,139 @@ async fn main() -> io::Result<()> {
         }
     }
 
+    if let Some(matches) = matches.subcommand_matches("run") {
+        let component_name = matches.get_one::<String>("component_name").unwrap();
+        match reactor.run_component(component_name).await {
+            Ok(_) => {
+                return Ok(());
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("exec") {
+        let component_name = matches.get_one::<String>("component_name").unwrap();
+        let command = matches.get_many::<String>("command").unwrap().cloned().collect::<Vec<_>>();
+        match reactor.exec(component_name, &command).await {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("shell") {
+        let component_name = matches.get_one::<String>("component_name").unwrap();
+        match reactor.shell(component_name).await {
+            Ok(code) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("export") {
+        if let Some(matches) = matches.subcommand_matches("compose") {
+            let output = matches.get_one::<String>("output").map(|output| invocation_dir.join(output));
+            match reactor.export_compose(output.as_deref()) {
+                Ok(path) => {
+                    println!("Wrote {}", path.display());
+                    return Ok(());
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("ctl") {
+        let socket_path = reactor.control_socket_path();
+        let component_name = |matches: &clap::ArgMatches| matches.get_one::<String>("component_name").cloned();
+        let request = match matches.subcommand() {
+            Some(("status", _)) => ControlRequest::Status,
+            Some(("restart", matches)) => ControlRequest::Restart { component: component_name(matches).unwrap() },
+            Some(("rebuild", matches)) => ControlRequest::Rebuild { component: component_name(matches).unwrap() },
+            Some(("stop", matches)) => ControlRequest::Stop { component: component_name(matches).unwrap() },
+            Some(("logs", matches)) => ControlRequest::Logs { component: component_name(matches) },
+            _ => return Ok(()),
+        };
+
+        let result = match request {
+            ControlRequest::Logs { component } => control::follow_logs(&socket_path, component).await,
+            request => control::send(&socket_path, &request).await.and_then(|response| {
+                if let Some(components) = &response.components {
+                    let width = components.iter().map(|c| c.component.len()).max().unwrap_or_default();
+                    for component in components {
+                        let status = component.status.as_ref().map(|s| s.describe()).unwrap_or("not started".to_string());
+                        let port = component.port.map(|p| format!("localhost:{}", p)).unwrap_or_default();
+                        println!("{:width$}  {:14}  {}", component.component.bold(), status, port, width = width);
+                    }
+                }
+                let message = response.message.unwrap_or_default();
+                if !response.ok {
+                    return Err(message);
+                }
+                if !message.is_empty() {
+                    println!("{}", message);
+                }
+                Ok(())
+            }),
+        };
+
+        match result {
+            Ok(_) => {
+                return Ok(());
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("volumes") {
+        let result = match matches.subcommand() {
+            Some(("snapshot", matches)) => {
+                let name = matches.get_one::<String>("name").unwrap();
+                let component_name = matches.get_one::<String>("component_name").map(|s| s.as_str());
+                reactor.snapshot_volumes(name, component_name).await
+            }
+            Some(("restore", matches)) => {
+                let name = matches.get_one::<String>("name").unwrap();
+                let component_name = matches.get_one::<String>("component_name").map(|s| s.as_str());
+                reactor.restore_volumes(name, component_name).await
+            }
+            Some(("list", matches)) => {
+                let name = matches.get_one::<String>("name");
+                reactor.list_snapshots().map(|snapshots| {
+                    for (snapshot, components) in snapshots {
+                        if name.is_some_and(|name| *name != snapshot) {
+                            continue;
+                        }
+                        println!("{}  {}", snapshot.white().bold(), components.join(", "));
+                    }
+                })
+            }
+            _ => Ok(()),
+        };
+
+        match result {
+            Ok(_) => {
+                return Ok(());
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
     
     if let Some(_) = matches.subcommand_matches("build") {
         match reactor.build().await {
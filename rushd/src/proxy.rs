@@ -0,0 +1,362 @@
+use crate::container::ServicesSpec;
+use colored::{ColoredString, Colorize};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// Where requests for a mount point, optionally on a subdomain, are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub component: String,
+    pub subdomain: Option<String>,
+    pub mount_point: String,
+    pub port: u16,
+}
+
+impl Route {
+    /// Routes for the services an ingress exposes. As in the nginx ingress,
+    /// services without a mount point are only reachable on their
+    /// subdomain, and services with neither are not routed.
+    pub fn from_services(services: &ServicesSpec, components: &[String]) -> Vec<Route> {
+        let mut routes = services
+            .values()
+            .filter(|service| components.contains(&service.name))
+            .filter(|service| service.mount_point.is_some() || service.subdomain.is_some())
+            .map(|service| Route {
+                component: service.name.clone(),
+                subdomain: service.subdomain.clone(),
+                mount_point: service.mount_point.clone().unwrap_or("/".to_string()),
+                port: service.port,
+            })
+            .collect::<Vec<_>>();
+        routes.sort_by(|a, b| (&a.subdomain, &a.mount_point).cmp(&(&b.subdomain, &b.mount_point)));
+        routes
+    }
+}
+
+/// Picks the route for a request. A host whose first label is a known
+/// subdomain only reaches the services on that subdomain, every other host
+/// reaches the services without one. Within those, the longest mount point
+/// the path starts with on a segment boundary wins.
+fn find_route<'a>(routes: &'a [Route], host: &str, path: &str) -> Option<&'a Route> {
+    let host = host.split(':').next().unwrap_or(host);
+    let label = host.split('.').next().unwrap_or(host);
+    let subdomain = routes
+        .iter()
+        .filter_map(|route| route.subdomain.as_deref())
+        .find(|subdomain| *subdomain == label && host.len() > label.len());
+
+    routes
+        .iter()
+        .filter(|route| route.subdomain.as_deref() == subdomain)
+        .filter(|route| matches_mount_point(path, &route.mount_point))
+        .max_by_key(|route| route.mount_point.len())
+}
+
+/// Whether `path` is `mount_point` or below it, so `/api` matches `/api`,
+/// `/api/users` and `/api?page=2` but not `/apiary`.
+fn matches_mount_point(path: &str, mount_point: &str) -> bool {
+    match path.strip_prefix(mount_point) {
+        Some(rest) => rest.is_empty() || mount_point.ends_with('/') || rest.starts_with(['/', '?']),
+        None => false,
+    }
+}
+
+struct RequestHead {
+    method: String,
+    path: String,
+    host: String,
+    upgrade: bool,
+    /// Raw header lines, without the request line
+    headers: Vec<String>,
+}
+
+impl RequestHead {
+    fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.split("\r\n").filter(|line| !line.is_empty());
+        let mut request_line = lines.next()?.split_whitespace();
+        let method = request_line.next()?.to_string();
+        let path = request_line.next()?.to_string();
+
+        let headers = lines.map(|line| line.to_string()).collect::<Vec<_>>();
+        let header = |name: &str| {
+            headers.iter().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+            })
+        };
+
+        let host = header("host").unwrap_or_default();
+        let upgrade = header("upgrade").is_some()
+            && header("connection").is_some_and(|value| value.to_lowercase().contains("upgrade"));
+
+        Some(RequestHead { method, path, host, upgrade, headers })
+    }
+
+    /// The head sent upstream. Plain requests are sent with
+    /// `Connection: close` so that every request on a client connection is
+    /// routed on its own; upgrades keep their headers so websockets work.
+    fn forwarded(&self, request_line: &str, client: &SocketAddr) -> String {
+        let hop_by_hop = ["connection", "keep-alive", "proxy-connection"];
+        let mut head = format!("{}\r\n", request_line);
+        let mut forwarded_for = client.ip().to_string();
+        for line in &self.headers {
+            let (name, value) = line.split_once(':').unwrap_or((line, ""));
+            let name = name.trim().to_lowercase();
+            if name == "x-forwarded-for" {
+                forwarded_for = format!("{}, {}", value.trim(), forwarded_for);
+                continue;
+            }
+            if !self.upgrade && hop_by_hop.contains(&name.as_str()) {
+                continue;
+            }
+            head.push_str(line);
+            head.push_str("\r\n");
+        }
+        if !self.upgrade {
+            head.push_str("Connection: close\r\n");
+        }
+        head.push_str(&format!("X-Real-IP: {}\r\n", client.ip()));
+        head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded_for));
+        head.push_str(&format!("X-Forwarded-Host: {}\r\n", self.host));
+        head.push_str("X-Forwarded-Proto: http\r\n\r\n");
+        head
+    }
+}
+
+/// The reverse proxy behind `ingress_mode: builtin`. It keeps running while
+/// the components are rebuilt and restarted, and its routes can be swapped
+/// without interrupting it.
+pub struct IngressProxy {
+    routes: Arc<RwLock<Vec<Route>>>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl IngressProxy {
+    pub async fn start(port: u16, routes: Vec<Route>, label: ColoredString) -> Result<Self, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .await
+            .map_err(|e| format!("Failed to bind ingress on port {}: {}", port, e))?;
+
+        println!("{} |   {}", label, format!("Listening on http://localhost:{}", port).bold().white());
+        for route in &routes {
+            println!("{} |   {}", label, Self::describe(route));
+        }
+
+        let routes = Arc::new(RwLock::new(routes));
+        let shared_routes = routes.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let (stream, client) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        eprintln!("{} |   Failed to accept connection: {}", label, e);
+                        continue;
+                    }
+                };
+
+                let routes = shared_routes.clone();
+                let label = label.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = Self::handle(stream, client, routes, &label).await {
+                        eprintln!("{} |   {}", label, e);
+                    }
+                });
+            }
+        });
+
+        Ok(IngressProxy { routes, handle })
+    }
+
+    fn describe(route: &Route) -> String {
+        let host = match &route.subdomain {
+            Some(subdomain) => format!("{}.*", subdomain),
+            None => "*".to_string(),
+        };
+        format!("{}{} -> {} (localhost:{})", host, route.mount_point, route.component, route.port)
+    }
+
+    pub fn set_routes(&self, routes: Vec<Route>) {
+        *self.routes.write().unwrap() = routes;
+    }
+
+    async fn handle(
+        mut client: TcpStream,
+        client_address: SocketAddr,
+        routes: Arc<RwLock<Vec<Route>>>,
+        label: &ColoredString,
+    ) -> Result<(), String> {
+        let mut buffer = Vec::new();
+        let head_end = loop {
+            if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                break position + 4;
+            }
+            if buffer.len() > MAX_HEAD_SIZE {
+                return Err("Request head too large".to_string());
+            }
+
+            let mut chunk = [0u8; 4096];
+            let read = client.read(&mut chunk).await.map_err(|e| e.to_string())?;
+            if read == 0 {
+                // Closed before sending a full request
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+        };
+
+        let head = String::from_utf8_lossy(&buffer[..head_end]).to_string();
+        let request = match RequestHead::parse(&head) {
+            Some(request) => request,
+            None => return Self::respond(client, "400 Bad Request", "Malformed request").await,
+        };
+
+        let route = find_route(&routes.read().unwrap(), &request.host, &request.path).cloned();
+        let route = match route {
+            Some(route) => route,
+            None => {
+                println!("{} |   {} {}{} -> {}", label, request.method, request.host, request.path, "404".yellow());
+                let message = format!("No route for {}{}", request.host, request.path);
+                return Self::respond(client, "404 Not Found", &message).await;
+            }
+        };
+
+        let upstream = match TcpStream::connect(("127.0.0.1", route.port)).await {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                println!("{} |   {} {} -> {} {}", label, request.method, request.path, route.component, "502".red());
+                let message = format!("{} is not reachable on port {}: {}", route.component, route.port, e);
+                return Self::respond(client, "502 Bad Gateway", &message).await;
+            }
+        };
+
+        let request_line = head.split("\r\n").next().unwrap_or_default();
+        let mut forwarded = request.forwarded(request_line, &client_address).into_bytes();
+        forwarded.extend_from_slice(&buffer[head_end..]);
+
+        let (mut client_read, mut client_write) = client.into_split();
+        let (mut upstream_read, mut upstream_write) = upstream.into_split();
+        upstream_write.write_all(&forwarded).await.map_err(|e| e.to_string())?;
+
+        // The request body, and for upgraded connections everything the
+        // client sends, is forwarded while the response is relayed.
+        let request_task = tokio::spawn(async move {
+            let _ = tokio::io::copy(&mut client_read, &mut upstream_write).await;
+            let _ = upstream_write.shutdown().await;
+        });
+
+        let mut chunk = vec![0u8; 8192];
+        let read = upstream_read.read(&mut chunk).await.unwrap_or(0);
+        let status = String::from_utf8_lossy(&chunk[..read])
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or("-")
+            .to_string();
+        let colored_status = match status.chars().next() {
+            Some('2') | Some('1') | Some('3') => status.green(),
+            Some('4') => status.yellow(),
+            _ => status.red(),
+        };
+        println!("{} |   {} {} -> {} {}", label, request.method, request.path, route.component, colored_status);
+
+        if read > 0 {
+            let _ = client_write.write_all(&chunk[..read]).await;
+            let _ = tokio::io::copy(&mut upstream_read, &mut client_write).await;
+        }
+        let _ = client_write.shutdown().await;
+        request_task.abort();
+
+        Ok(())
+    }
+
+    async fn respond(mut client: TcpStream, status: &str, message: &str) -> Result<(), String> {
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            message.len(),
+            message
+        );
+        client.write_all(response.as_bytes()).await.map_err(|e| e.to_string())?;
+        let _ = client.shutdown().await;
+        Ok(())
+    }
+}
+
+impl Drop for IngressProxy {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(component: &str, subdomain: Option<&str>, mount_point: &str) -> Route {
+        Route {
+            component: component.to_string(),
+            subdomain: subdomain.map(|s| s.to_string()),
+            mount_point: mount_point.to_string(),
+            port: 8000,
+        }
+    }
+
+    fn routed<'a>(routes: &'a [Route], host: &str, path: &str) -> Option<&'a str> {
+        find_route(routes, host, path).map(|route| route.component.as_str())
+    }
+
+    #[test]
+    fn mount_points_match_on_segment_boundaries() {
+        assert!(matches_mount_point("/api", "/api"));
+        assert!(matches_mount_point("/api/users", "/api"));
+        assert!(matches_mount_point("/api?page=2", "/api"));
+        assert!(!matches_mount_point("/apiary", "/api"));
+        assert!(!matches_mount_point("/", "/api"));
+        assert!(matches_mount_point("/anything", "/"));
+        assert!(matches_mount_point("/api/users", "/api/"));
+    }
+
+    #[test]
+    fn longest_mount_point_wins() {
+        let routes = [route("frontend", None, "/"), route("backend", None, "/api")];
+
+        assert_eq!(routed(&routes, "localhost:9000", "/api/users"), Some("backend"));
+        assert_eq!(routed(&routes, "localhost:9000", "/apiary"), Some("frontend"));
+        assert_eq!(routed(&routes, "localhost:9000", "/"), Some("frontend"));
+    }
+
+    #[test]
+    fn subdomains_only_reach_their_services() {
+        let routes = [route("frontend", None, "/"), route("docs", Some("docs"), "/")];
+
+        assert_eq!(routed(&routes, "docs.localhost:9000", "/"), Some("docs"));
+        assert_eq!(routed(&routes, "localhost:9000", "/"), Some("frontend"));
+        assert_eq!(routed(&routes, "docs", "/"), Some("frontend"));
+
+        let routes = [route("docs", Some("docs"), "/guide")];
+        assert_eq!(routed(&routes, "docs.localhost", "/"), None);
+    }
+
+    #[test]
+    fn appends_to_forwarded_for() {
+        let client: SocketAddr = "10.0.0.2:5000".parse().unwrap();
+        let head = RequestHead::parse("GET / HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-For: 1.2.3.4\r\n\r\n").unwrap();
+
+        let forwarded = head.forwarded("GET / HTTP/1.1", &client);
+        let values = forwarded
+            .lines()
+            .filter(|line| line.to_lowercase().starts_with("x-forwarded-for:"))
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["X-Forwarded-For: 1.2.3.4, 10.0.0.2"]);
+    }
+
+    #[test]
+    fn sets_forwarded_for_without_one() {
+        let client: SocketAddr = "10.0.0.2:5000".parse().unwrap();
+        let head = RequestHead::parse("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        assert!(head.forwarded("GET / HTTP/1.1", &client).contains("X-Forwarded-For: 10.0.0.2\r\n"));
+    }
+}
@@ -17,6 +17,7 @@ pub struct ToolchainContext {
     trunk: String,
     kubectl: Option<String>,
     minikube: Option<String>,
+    kind: Option<String>,
 
     // Secondary
     cc: String,
@@ -47,6 +48,7 @@ impl ToolchainContext {
             .expect("trunk not found."),
             kubectl: first_which(vec!["kubectl"]),
             minikube: first_which(vec!["minikube"]),
+            kind: first_which(vec!["kind"]),
 
             cc: first_which(vec!["clang", "gcc"])
                 .expect("None of the default toolchains are availablefor this architecture"),
@@ -117,6 +119,7 @@ impl ToolchainContext {
                 .expect("trunk not found."),
                 kubectl: first_which(vec!["kubectl"]),
                 minikube: first_which(vec!["minikube"]),
+                kind: first_which(vec!["kind"]),
 
                 cc,
                 cxx,
@@ -197,6 +200,10 @@ impl ToolchainContext {
         self.minikube.clone()
     }
 
+    pub fn kind(&self) -> Option<String> {
+        self.kind.clone()
+    }
+
     pub fn docker(&self) -> &str {
         &self.docker
     }
@@ -0,0 +1,134 @@
+use crate::gitignore::GitIgnore;
+use notify::{Config as NotifyConfig, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::time::{Duration, Instant};
+
+/// A batch of file changes that arrived within one debounce window.
+#[derive(Debug, Clone)]
+pub struct ChangeSet {
+    paths: BTreeSet<PathBuf>,
+}
+
+impl ChangeSet {
+    pub fn paths(&self) -> &BTreeSet<PathBuf> {
+        &self.paths
+    }
+}
+
+/// Watches a directory recursively and turns the raw notify events into
+/// debounced change sets. Paths ignored by git and rushd's own outputs
+/// never trigger a change.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    receiver: UnboundedReceiver<notify::Result<Event>>,
+    gitignore: GitIgnore,
+    excluded: Vec<PathBuf>,
+    debounce: Duration,
+    pending: BTreeSet<PathBuf>,
+    deadline: Option<Instant>,
+}
+
+impl FileWatcher {
+    pub fn new(directory: &Path, excluded: Vec<PathBuf>, debounce: Duration) -> Result<Self, String> {
+        let directory = directory
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", directory.display(), e))?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut watcher = RecommendedWatcher::new(
+            move |event| {
+                let _ = sender.send(event);
+            },
+            NotifyConfig::default(),
+        )
+        .map_err(|e| e.to_string())?;
+
+        watcher
+            .watch(&directory, RecursiveMode::Recursive)
+            .map_err(|e| e.to_string())?;
+
+        let excluded = excluded.iter().map(|path| absolute_path(path)).collect();
+
+        Ok(FileWatcher {
+            _watcher: watcher,
+            receiver,
+            gitignore: GitIgnore::new(&directory),
+            excluded,
+            debounce,
+            pending: BTreeSet::new(),
+            deadline: None,
+        })
+    }
+
+    fn is_relevant(&self, path: &Path) -> bool {
+        if self.excluded.iter().any(|excluded| path.starts_with(excluded)) {
+            return false;
+        }
+
+        // Deleted files are relevant as well, directories are not
+        (path.is_file() || !path.exists()) && !self.gitignore.ignores(path)
+    }
+
+    /// Waits for the next batch of changes. The window is extended every time
+    /// a new relevant event arrives, so a save touching several files results
+    /// in a single change set. The future is cancel safe: events collected
+    /// before it is dropped are kept for the next call.
+    ///
+    /// Returns `None` once the underlying watcher has stopped.
+    pub async fn next_change_set(&mut self) -> Option<ChangeSet> {
+        loop {
+            let event = match self.deadline {
+                None => self.receiver.recv().await,
+                Some(deadline) => match tokio::time::timeout_at(deadline, self.receiver.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        self.deadline = None;
+                        let paths = std::mem::take(&mut self.pending);
+                        return Some(ChangeSet { paths });
+                    }
+                },
+            };
+
+            let event = match event? {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Watch error: {:?}", e);
+                    continue;
+                }
+            };
+
+            if let EventKind::Access(_) = event.kind {
+                continue;
+            }
+
+            let paths = event
+                .paths
+                .into_iter()
+                .filter(|path| self.is_relevant(path))
+                .collect::<Vec<_>>();
+
+            if !paths.is_empty() {
+                self.pending.extend(paths);
+                self.deadline = Some(Instant::now() + self.debounce);
+            }
+        }
+    }
+}
+
+fn absolute_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+
+    // Outputs that do not exist yet: resolve the closest existing ancestor
+    for ancestor in path.ancestors().skip(1) {
+        if let Ok(base) = ancestor.canonicalize() {
+            if let Ok(rest) = path.strip_prefix(ancestor) {
+                return base.join(rest);
+            }
+        }
+    }
+    path.to_path_buf()
+}